@@ -0,0 +1,38 @@
+use crate::rule::Rule;
+use crate::topology::Topology;
+
+/// A way of evolving a pattern, so that a universe can switch between engines
+/// that are fast at different things and carry its pattern across.
+///
+/// Engines store the cells that differ from the background, see
+/// `background`: while a `B0` rule has the background on, a stored cell in
+/// state 1 is a dead one. `get_cell`, `set_cell` and `population` deal in the
+/// cells as they are seen instead.
+pub trait Algorithm {
+    /// Fails, keeping the old rule, when the engine can't run the rule on the
+    /// grid.
+    fn set_rule(&mut self, rule: Rule, topology: Topology) -> Result<(), String>;
+
+    /// Replaces the pattern with the stored cells of another engine, as `(x,
+    /// y, state)`.
+    fn load(&mut self, cells: &[(i64, i64, u8)], background: bool);
+
+    /// The stored cells that are not in state 0, as `(x, y, state)`.
+    fn cells(&self) -> Vec<(i64, i64, u8)>;
+
+    /// Whether a `B0` rule has turned the cells around the pattern on.
+    fn background(&self) -> bool;
+
+    fn step(&mut self, n: u64);
+
+    fn get_cell(&self, x: i64, y: i64) -> u8;
+
+    fn set_cell(&mut self, x: i64, y: i64, state: u8);
+
+    /// The smallest and largest x and y of a stored cell, as `[min_x, max_x,
+    /// min_y, max_y]`, or `None` when there are none.
+    fn bounds(&self) -> Option<[i64; 4]>;
+
+    /// The number of visible live cells, saturating at `u64::MAX`.
+    fn population(&self) -> u64;
+}
@@ -0,0 +1,333 @@
+use std::hash::Hash;
+use std::ops::Index;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::leaf::LEAF_LEVEL;
+
+/// The index of a node in its `Arena`.
+pub type NodeId = u32;
+
+/// A square of 2^level cells, made of four quadrants above the leaves. Nodes
+/// refer to each other by id, so they are plain data that can be copied or
+/// written out as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    /// The NW, NE, SW and SE quadrants.
+    pub a: NodeId,
+    pub b: NodeId,
+    pub c: NodeId,
+    pub d: NodeId,
+    /// Every cell that is not in state 0, including decaying ones. It
+    /// saturates past level 31, see `exact_population` for the real count.
+    pub population: u64,
+    /// The cells of a leaf, see `LEAF_LEVEL`.
+    pub bits: u64,
+    // one past the index of a leaf's states in `Arena::states`, or 0 when none
+    // of its cells are decaying
+    states: u32,
+    pub level: u8,
+}
+
+/// A hash map that can be filled through a shared reference, and from several
+/// threads at once with the `parallel` feature.
+#[derive(Debug)]
+pub struct Table<K: Eq + Hash, V> {
+    #[cfg(not(feature = "parallel"))]
+    map: std::cell::RefCell<std::collections::HashMap<K, V>>,
+    #[cfg(feature = "parallel")]
+    map: dashmap::DashMap<K, V>,
+}
+
+impl<K: Eq + Hash, V> Default for Table<K, V> {
+    fn default() -> Self {
+        Table { map: Default::default() }
+    }
+}
+
+impl<K: Eq + Hash, V: Copy> Table<K, V> {
+    /// What the table takes up. Its slots come in powers of two and at most
+    /// 7/8 of them are used, with a control byte each.
+    pub fn bytes(&self) -> usize {
+        self.capacity() / 7 * 8 * (std::mem::size_of::<(K, V)>() + 1)
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<K: Eq + Hash, V: Copy> Table<K, V> {
+    pub fn len(&self) -> usize {
+        self.map.borrow().len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.map.borrow().capacity()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.map.borrow().get(key).copied()
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.map.borrow_mut().insert(key, value);
+    }
+
+    /// The value of `key`, made by `make` when there is none yet, and whether
+    /// it was already there.
+    pub fn get_or_insert_with(&self, key: K, make: impl FnOnce() -> V) -> (V, bool) {
+        match self.map.borrow_mut().entry(key) {
+            std::collections::hash_map::Entry::Occupied(entry) => (*entry.get(), true),
+            std::collections::hash_map::Entry::Vacant(entry) => (*entry.insert(make()), false),
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<K: Eq + Hash, V: Copy> Table<K, V> {
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.map.get(key).map(|value| *value)
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.map.insert(key, value);
+    }
+
+    /// The value of `key`, made by `make` when there is none yet, and whether
+    /// it was already there. The entry stays locked while `make` runs, so two
+    /// threads never both make it.
+    pub fn get_or_insert_with(&self, key: K, make: impl FnOnce() -> V) -> (V, bool) {
+        match self.map.entry(key) {
+            dashmap::Entry::Occupied(entry) => (*entry.get(), true),
+            dashmap::Entry::Vacant(entry) => (*entry.insert(make()), false),
+        }
+    }
+}
+
+/// Every node of a universe, each stored once: joining the same quadrants or
+/// making a leaf of the same cells again gives back the same id, so two
+/// nodes are the same pattern exactly when their ids are equal.
+///
+/// Nodes are added through a shared reference and never move, so with the
+/// `parallel` feature several threads can build on the arena at once.
+/// Children are always stored before their parents.
+#[derive(Debug, Default)]
+pub struct Arena {
+    nodes: boxcar::Vec<Node>,
+    states: boxcar::Vec<[u8; 64]>,
+
+    joins: Table<[NodeId; 4], NodeId>,
+    leaves: Table<u64, NodeId>,
+    decaying: Table<[u8; 64], NodeId>,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+// the memory taken by the first `len` items of a boxcar::Vec, which adds
+// buckets of 32, 64, 128, .. slots, each slot flagged as taken
+fn vec_bytes<T>(len: usize) -> usize {
+    let slots = (len + 32).next_power_of_two() - 32;
+    slots * std::mem::size_of::<(T, std::sync::atomic::AtomicBool)>()
+}
+
+impl Index<NodeId> for Arena {
+    type Output = Node;
+
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id as usize]
+    }
+}
+
+impl Arena {
+    pub fn len(&self) -> usize {
+        self.nodes.count()
+    }
+
+    /// The nodes above the leaves.
+    pub fn joins(&self) -> usize {
+        self.joins.len()
+    }
+
+    pub fn bytes(&self) -> usize {
+        vec_bytes::<Node>(self.len()) + vec_bytes::<[u8; 64]>(self.states.count())
+            + self.joins.bytes() + self.leaves.bytes() + self.decaying.bytes()
+    }
+
+    /// How many times an existing node was asked for again.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// How many nodes were ever added.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn children(&self, id: NodeId) -> [NodeId; 4] {
+        let node = &self[id];
+        [node.a, node.b, node.c, node.d]
+    }
+
+    /// The states of the cells of a leaf, in reading order.
+    pub fn cells(&self, id: NodeId) -> [u8; 64] {
+        let node = &self[id];
+        match node.states {
+            0 => std::array::from_fn(|i| (node.bits >> i & 1) as u8),
+            states => self.states[states as usize - 1],
+        }
+    }
+
+    /// Whether some cells of a leaf are decaying, so that its bits alone do
+    /// not tell their states.
+    pub fn is_decaying(&self, id: NodeId) -> bool {
+        self[id].states != 0
+    }
+
+    fn push(&self, node: Node) -> NodeId {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        NodeId::try_from(self.nodes.push(node)).expect("too many nodes")
+    }
+
+    fn found(&self, (id, found): (NodeId, bool)) -> NodeId {
+        if found {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        id
+    }
+
+    pub fn join(&self, a: NodeId, b: NodeId, c: NodeId, d: NodeId) -> NodeId {
+        self.found(self.joins.get_or_insert_with([a, b, c, d], || {
+            let population = [a, b, c, d].iter()
+                .fold(0_u64, |acc, &n| acc.saturating_add(self[n].population));
+            self.push(Node {
+                a, b, c, d,
+                population,
+                bits: 0,
+                states: 0,
+                level: self[a].level + 1,
+            })
+        }))
+    }
+
+    pub fn leaf_bits(&self, bits: u64) -> NodeId {
+        self.found(self.leaves.get_or_insert_with(bits, || self.push(Node {
+            a: 0, b: 0, c: 0, d: 0,
+            population: u64::from(bits.count_ones()),
+            bits,
+            states: 0,
+            level: LEAF_LEVEL,
+        })))
+    }
+
+    pub fn leaf(&self, cells: &[u8; 64]) -> NodeId {
+        let bits = cells.iter().enumerate()
+            .filter(|(_, &state)| state != 0)
+            .fold(0, |acc, (i, _)| acc | 1 << i);
+        if cells.iter().all(|&state| state <= 1) {
+            return self.leaf_bits(bits);
+        }
+
+        self.found(self.decaying.get_or_insert_with(*cells, || {
+            let states = self.states.push(*cells) + 1;
+            self.push(Node {
+                a: 0, b: 0, c: 0, d: 0,
+                population: u64::from(bits.count_ones()),
+                bits,
+                states: u32::try_from(states).expect("too many leaves"),
+                level: LEAF_LEVEL,
+            })
+        }))
+    }
+
+    // whether each node is part of one of `roots`
+    fn mark(&self, roots: &[NodeId]) -> Vec<bool> {
+        // parents come after their children, so one pass from the end marks
+        // everything below the roots
+        let mut live = vec![false; self.len()];
+        for &root in roots {
+            live[root as usize] = true;
+        }
+        for id in (0..self.len()).rev() {
+            let node = self.nodes[id];
+            if live[id] && node.level > LEAF_LEVEL {
+                for child in [node.a, node.b, node.c, node.d] {
+                    live[child as usize] = true;
+                }
+            }
+        }
+        live
+    }
+
+    /// Roughly what the nodes of `roots` cost, counting nodes they share once,
+    /// as their share of the whole arena.
+    pub fn bytes_of(&self, roots: &[NodeId]) -> usize {
+        let live = self.mark(roots).into_iter().filter(|&live| live).count();
+        self.bytes() / self.len().max(1) * live
+    }
+
+    /// Drops every node that none of `roots` is made of, and moves the rest
+    /// together. The roots are updated to their new ids, all other ids are
+    /// no longer valid.
+    pub fn retain(&mut self, roots: &mut [NodeId]) {
+        let live = self.mark(roots);
+
+        let old = std::mem::take(self);
+        let mut ids = vec![0; old.len()];
+        for (id, node) in old.nodes.iter().filter(|&(id, _)| live[id]) {
+            ids[id] = if node.level > LEAF_LEVEL {
+                let [a, b, c, d] = [node.a, node.b, node.c, node.d].map(|child| ids[child as usize]);
+                self.join(a, b, c, d)
+            }
+            else {
+                self.leaf(&old.cells(id as NodeId))
+            };
+        }
+        for root in roots.iter_mut() {
+            *root = ids[*root as usize];
+        }
+        self.hits = old.hits;
+        self.misses = old.misses;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Universe;
+
+    #[test]
+    fn stores_each_node_once() {
+        let nodes = Arena::default();
+        let (empty, full) = (nodes.leaf_bits(0), nodes.leaf_bits(u64::MAX));
+        let node = nodes.join(empty, full, full, empty);
+        assert_eq!(nodes.join(empty, full, full, empty), node);
+        assert_ne!(nodes.join(full, empty, empty, full), node);
+        assert_eq!(nodes.leaf(&[1; 64]), full);
+
+        let mut cells = [1; 64];
+        cells[0] = 2;
+        assert_eq!(nodes.leaf(&cells), nodes.leaf(&cells));
+        assert_eq!(nodes.cells(nodes.leaf(&cells)), cells);
+        assert_eq!((nodes.len(), nodes.joins()), (5, 2));
+        assert_eq!((nodes.hits(), nodes.misses()), (4, 5));
+    }
+
+    #[test]
+    fn shares_repeated_subtrees() {
+        // a square of 32 by 32 blocks, one in each leaf
+        let pts: Vec<i64> = (0..32 * 32).flat_map(|i| {
+            let (x, y) = (i % 32 * 8, i / 32 * 8);
+            [x, y, x + 1, y, x, y + 1, x + 1, y + 1]
+        }).collect();
+        let mut universe = Universe::new();
+        universe.construct(pts);
+        assert_eq!(universe.population(), 4 * 32 * 32);
+        assert!(universe.hashlife().nodes().len() < 40, "{}", universe.hashlife().nodes().len());
+    }
+}
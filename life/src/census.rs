@@ -0,0 +1,424 @@
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+use wasm_bindgen::prelude::*;
+
+use crate::algorithm::Algorithm;
+use crate::hashlife::Hashlife;
+use crate::parser::{to_apgcode, to_rle};
+use crate::period::{find_period, Periodicity};
+use crate::rule::Rule;
+use crate::topology::Topology;
+use crate::Universe;
+
+pub(crate) type Cells = Vec<(i64, i64, u8)>;
+
+// a reflection or rotation about the origin
+pub(crate) type Symmetry = fn(i64, i64) -> (i64, i64);
+
+/// One kind of object in a census, and how many of them there are. Objects
+/// that didn't repeat within the time given have no kind and period 0.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CensusEntry {
+    /// A common name such as `block` or `glider` in Life, or else what kind
+    /// of object it is.
+    pub name: String,
+    pub kind: Option<Periodicity>,
+    pub period: u64,
+    /// The live cells of the phase that `rle` shows.
+    pub population: u64,
+    pub count: u64,
+    pub rle: String,
+    /// Empty for objects that have none, see `to_apgcode`.
+    pub apgcode: String,
+}
+
+// some common objects in Life, the rest are named after what they are
+static LIFE_OBJECTS: &[(&str, &str)] = &[
+    ("block", "2o$2o!"),
+    ("beehive", "b2o$o2bo$b2o!"),
+    ("loaf", "b2o$o2bo$bobo$2bo!"),
+    ("boat", "2o$obo$bo!"),
+    ("ship", "2o$obo$b2o!"),
+    ("tub", "bo$obo$bo!"),
+    ("pond", "b2o$o2bo$o2bo$b2o!"),
+    ("long boat", "2o$obo$bobo$2bo!"),
+    ("barge", "bo$obo$bobo$2bo!"),
+    ("mango", "b2o$o2bo$bo2bo$2b2o!"),
+    ("eater 1", "2o$obo$2bo$2b2o!"),
+    ("snake", "2obo$ob2o!"),
+    ("aircraft carrier", "2o$o2bo$2b2o!"),
+    ("blinker", "3o!"),
+    ("toad", "b3o$3o!"),
+    ("beacon", "2o$2o$2b2o$2b2o!"),
+    ("clock", "2bo$obo$bobo$bo!"),
+    ("pulsar", "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!"),
+    ("pentadecathlon", "2bo4bo$2ob4ob2o$2bo4bo!"),
+    ("glider", "bo$2bo$3o!"),
+    ("lightweight spaceship", "bo2bo$o4b$o3bo$4o!"),
+    ("middleweight spaceship", "3bo$bo3bo$o$o4bo$5o!"),
+    ("heavyweight spaceship", "3b2o$bo4bo$o$o5bo$6o!"),
+];
+
+static LIFE_NAMES: Lazy<HashMap<Cells, &'static str>> = Lazy::new(|| {
+    let rule = Rule::life();
+    LIFE_OBJECTS.iter().map(|&(name, rle)| {
+        let cells: Cells = parse_cells(rle);
+        let period = find_period(rule, Topology::default(), &cells, false, 100).expect("a known object");
+        (canonical(&rule, &evolve(&rule, &cells, period.period - 1)), name)
+    }).collect()
+});
+
+fn parse_cells(rle: &str) -> Cells {
+    let (positions, states, _) = crate::Life::parse_rle_pattern(rle);
+    positions.chunks(2).zip(states).map(|(c, state)| (c[0], c[1], state)).collect()
+}
+
+// the offsets of the neighbors of a cell
+fn neighbors(rule: &Rule) -> Vec<(i64, i64)> {
+    (0..9).filter(|i| rule.neighbors() >> i & 1 == 1).map(|i| (i % 3 - 1, i / 3 - 1)).collect()
+}
+
+// the offsets of the cells that are neighbors of a cell or share a neighbor
+// with it, so that they can take part in the same birth
+fn reach(rule: &Rule) -> Vec<(i64, i64)> {
+    let mut around = neighbors(rule);
+    around.push((0, 0));
+    let offsets: HashSet<(i64, i64)> = around.iter()
+        .flat_map(|&(x1, y1)| around.iter().map(move |&(x2, y2)| (x1 + x2, y1 + y2)))
+        .filter(|&offset| offset != (0, 0))
+        .collect();
+    offsets.into_iter().collect()
+}
+
+// the groups of cells that can be reached from each other in steps of one of
+// the offsets
+fn components(cells: &[(i64, i64, u8)], offsets: &[(i64, i64)]) -> Vec<Cells> {
+    let index: HashMap<(i64, i64), usize> = cells.iter().enumerate().map(|(i, &(x, y, _))| ((x, y), i)).collect();
+    let mut component = vec![usize::MAX; cells.len()];
+    let mut output = Vec::new();
+
+    for start in 0..cells.len() {
+        if component[start] != usize::MAX { continue }
+
+        component[start] = output.len();
+        let mut group = Vec::new();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            let (x, y, _) = cells[i];
+            group.push(cells[i]);
+            for &(dx, dy) in offsets {
+                if let Some(&j) = index.get(&(x + dx, y + dy)) {
+                    if component[j] == usize::MAX {
+                        component[j] = output.len();
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+        output.push(group);
+    }
+    output
+}
+
+// the groups of cells whose histories come close enough for the rule to have
+// them interact within `generations`. Cells within reach of each other in a
+// generation go together, and so do cells and the neighbors they had in the
+// generation before, so that parts that only meet later in a period, like
+// the halves of some phases of the pentadecathlon, still end up together
+fn interacting(rule: &Rule, cells: &[(i64, i64, u8)], generations: u64) -> Vec<Cells> {
+    let phases = evolve(rule, cells, generations);
+    let mut index: HashMap<(usize, i64, i64), usize> = HashMap::new();
+    for (generation, phase) in phases.iter().enumerate() {
+        for &(x, y, _) in phase {
+            let next = index.len();
+            index.insert((generation, x, y), next);
+        }
+    }
+
+    fn find(group: &mut [usize], i: usize) -> usize {
+        if group[i] != i {
+            group[i] = find(group, group[i]);
+        }
+        group[i]
+    }
+    let mut group: Vec<usize> = (0..index.len()).collect();
+    let (reach, mut parents) = (reach(rule), neighbors(rule));
+    parents.push((0, 0));
+    for (generation, phase) in phases.iter().enumerate() {
+        for &(x, y, _) in phase {
+            let i = index[&(generation, x, y)];
+            let close = reach.iter().map(|&(dx, dy)| (generation, x + dx, y + dy));
+            let before = parents.iter().filter_map(|&(dx, dy)| Some((generation.checked_sub(1)?, x + dx, y + dy)));
+            for j in close.chain(before).filter_map(|cell| index.get(&cell).copied()) {
+                let (a, b) = (find(&mut group, i), find(&mut group, j));
+                group[a] = b;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Cells> = HashMap::new();
+    for &(x, y, state) in cells {
+        let root = find(&mut group, index[&(0, x, y)]);
+        groups.entry(root).or_default().push((x, y, state));
+    }
+    groups.into_values().collect()
+}
+
+// the sorted cells of each of the first `generations` + 1 generations
+pub(crate) fn evolve(rule: &Rule, cells: &[(i64, i64, u8)], generations: u64) -> Vec<Cells> {
+    let mut hashlife = Hashlife::new();
+    hashlife.set_rule(*rule, Topology::default()).expect("an infinite plane runs any rule");
+    hashlife.load(cells, false);
+
+    let mut phases = Vec::new();
+    for generation in 0..=generations {
+        if generation > 0 {
+            hashlife.step(1);
+        }
+        let mut phase = hashlife.cells();
+        phase.sort_by_key(|&(x, y, _)| (y, x));
+        phases.push(phase);
+    }
+    phases
+}
+
+// the reflections and rotations that map the neighborhood onto itself, which
+// is all 8 of them except for hexagonal rules
+pub(crate) fn symmetries(rule: &Rule) -> Vec<Symmetry> {
+    let all: [Symmetry; 8] = [
+        |x, y| (x, y), |x, y| (-x, y), |x, y| (x, -y), |x, y| (-x, -y),
+        |x, y| (y, x), |x, y| (-y, x), |x, y| (y, -x), |x, y| (-y, -x),
+    ];
+    let neighbors = neighbors(rule);
+    all.into_iter().filter(|map| neighbors.iter().all(|&(x, y)| neighbors.contains(&map(x, y)))).collect()
+}
+
+// the smallest of the phases under every symmetry, moved to the origin, so
+// that an object gets the same cells in any phase and orientation
+fn canonical(rule: &Rule, phases: &[Cells]) -> Cells {
+    let symmetries = symmetries(rule);
+    phases.iter().flat_map(|phase| symmetries.iter().map(move |map| {
+        let mut cells: Cells = phase.iter().map(|&(x, y, state)| {
+            let (x, y) = map(x, y);
+            (x, y, state)
+        }).collect();
+        let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+        let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+        for cell in cells.iter_mut() {
+            (cell.0, cell.1) = (cell.0 - min_x, cell.1 - min_y);
+        }
+        cells.sort_by_key(|&(x, y, state)| (y, x, state));
+        cells
+    })).min().unwrap_or_default()
+}
+
+// whether the cells evolve the same for `generations` when run together as
+// they do apart
+fn is_independent(rule: &Rule, parts: &[&[(i64, i64, u8)]], generations: u64) -> bool {
+    let together: Cells = parts.concat();
+    let apart: Vec<Vec<Cells>> = parts.iter().map(|part| evolve(rule, part, generations)).collect();
+    evolve(rule, &together, generations).into_iter().enumerate().all(|(generation, phase)| {
+        let mut union: Cells = apart.iter().flat_map(|phases| phases[generation].iter().copied()).collect();
+        union.sort_by_key(|&(x, y, _)| (y, x));
+        union == phase
+    })
+}
+
+// splits a group of cells that are close enough to interact into the objects
+// that don't. Each object grows from one of the touching parts of the group
+// until it evolves the same with the rest of the group as without it for
+// `generations`, since some parts only interact three at a time. With
+// `periodic` it also has to repeat on its own within that time, or else a
+// part that dies without touching the rest would count as an object
+fn separate(rule: &Rule, group: Cells, generations: u64, periodic: bool) -> Vec<Cells> {
+    let parts = components(&group, &neighbors(rule));
+    if parts.len() == 1 {
+        return vec![group];
+    }
+
+    let reach = reach(rule);
+    let is_close = |object: &Cells, part: &Cells| {
+        let cells: HashSet<(i64, i64)> = object.iter().map(|&(x, y, _)| (x, y)).collect();
+        part.iter().any(|&(x, y, _)| reach.iter().any(|&(dx, dy)| cells.contains(&(x + dx, y + dy))))
+    };
+    let is_apart = |object: &Cells, others: &[usize]| {
+        let others: Cells = others.iter().flat_map(|&i| parts[i].iter().copied()).collect();
+        is_independent(rule, &[object, &others], generations)
+    };
+    let repeats = |object: &Cells| !periodic || find_period(*rule, Topology::default(), object, false, generations)
+        .is_some_and(|period| period.start == 0);
+
+    let mut objects = Vec::new();
+    let mut left: Vec<usize> = (1..parts.len()).rev().collect();
+    let mut object = parts[0].clone();
+    loop {
+        if left.is_empty() || (is_apart(&object, &left) && repeats(&object)) {
+            objects.push(object);
+            match left.pop() {
+                Some(i) => object = parts[i].clone(),
+                None => break,
+            }
+            continue;
+        }
+
+        // the object is most likely missing a part close to it that completes
+        // it on its own, failing that those it interacts with one on one, and
+        // failing those every part close to it
+        let close: Vec<usize> = left.iter().copied().filter(|&i| is_close(&object, &parts[i])).collect();
+        let completes = close.iter().copied().find(|&i| {
+            let others: Vec<usize> = left.iter().copied().filter(|&j| j != i).collect();
+            let grown = [object.as_slice(), &parts[i]].concat();
+            is_apart(&grown, &others) && repeats(&grown)
+        });
+        let joining = match completes {
+            Some(i) => vec![i],
+            None => {
+                let pairs: Vec<usize> = close.iter().copied().filter(|&i| !is_apart(&object, &[i])).collect();
+                if !pairs.is_empty() { pairs } else if !close.is_empty() { close } else { left.clone() }
+            }
+        };
+        for &i in &joining {
+            object.extend_from_slice(&parts[i]);
+        }
+        left.retain(|i| !joining.contains(i));
+    }
+    objects
+}
+
+/// Splits the cells into objects and counts each kind, most common first.
+/// Objects are groups of cells that come close enough for the rule to have
+/// them interact within `max_period` generations, split further where their
+/// parts turn out to evolve on their own. Each is run for up to `max_period`
+/// generations to tell what it is.
+pub fn census(rule: &Rule, cells: &[(i64, i64, u8)], max_period: u64) -> Vec<CensusEntry> {
+    let mut entries: HashMap<Cells, CensusEntry> = HashMap::new();
+    for group in interacting(rule, cells, max_period) {
+        // a group that settles later is still one object, one that never
+        // repeats could be objects that are just passing each other
+        let objects = match find_period(*rule, Topology::default(), &group, false, max_period) {
+            Some(period) if period.start != 0 => vec![group],
+            Some(period) => separate(rule, group, period.period, true),
+            None => separate(rule, group, max_period, false),
+        };
+
+        for object in objects {
+            let period = find_period(*rule, Topology::default(), &object, false, max_period)
+                .filter(|period| period.start == 0);
+            let phases = evolve(rule, &object, period.map_or(0, |period| period.period - 1));
+            let shape = canonical(rule, &phases);
+
+            let entry = entries.entry(shape.clone()).or_insert_with(|| {
+                let (kind, period) = (period.map(|period| period.kind), period.map_or(0, |period| period.period));
+                let name = match (*rule == Rule::life()).then(|| LIFE_NAMES.get(&shape)).flatten() {
+                    Some(name) => name.to_string(),
+                    None => match kind {
+                        Some(Periodicity::StillLife) => "still life".to_string(),
+                        Some(Periodicity::Oscillator) => format!("p{} oscillator", period),
+                        Some(Periodicity::Spaceship) => format!("p{} spaceship", period),
+                        Some(Periodicity::Empty) | None => "unsettled object".to_string(),
+                    },
+                };
+                let rle = to_rle(shape.clone(), rule.states() > 2).0;
+                let apgcode = to_apgcode(rule, &shape, max_period).unwrap_or_default();
+                CensusEntry { name, kind, period, population: shape.len() as u64, count: 0, rle, apgcode }
+            });
+            entry.count += 1;
+        }
+    }
+
+    let mut entries: Vec<CensusEntry> = entries.into_values().collect();
+    entries.sort_by(|a, b| b.count.cmp(&a.count).then(a.population.cmp(&b.population)).then(a.rle.cmp(&b.rle)));
+    entries
+}
+
+#[wasm_bindgen]
+impl Universe {
+    /// Splits the pattern into objects and counts each kind, see `census`.
+    /// Meant for a soup that has settled, where every object repeats within
+    /// `max_period` generations.
+    pub fn census(&self, max_period: u64) -> Result<Vec<CensusEntry>, String> {
+        if self.topology.is_bounded() {
+            return Err("A census needs an infinite plane".to_string());
+        }
+        if self.engine().background() {
+            return Err("A census needs the background to be dead".to_string());
+        }
+        Ok(census(&self.rule, &self.engine().cells(), max_period))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Life;
+
+    #[test]
+    fn counts_known_objects() {
+        let mut universe = Universe::new();
+        // two blocks and one of each other object, far enough apart not to meet
+        for (i, code) in ["xs4_33", "xp2_7", "xq4_153", "xq4_6frc", "xs4_33"].iter().enumerate() {
+            let pts = Life::parse_apgcode(code.to_string()).unwrap();
+            for c in pts.chunks(2) {
+                universe.set_cell(c[0] + 40 * i as i64, c[1], true);
+            }
+        }
+
+        let entries = universe.census(16).unwrap();
+        let summary: Vec<(&str, Option<Periodicity>, u64, u64, &str)> = entries.iter()
+            .map(|entry| (entry.name.as_str(), entry.kind, entry.period, entry.count, entry.apgcode.as_str()))
+            .collect();
+        assert_eq!(summary, [
+            ("block", Some(Periodicity::StillLife), 1, 2, "xs4_33"),
+            ("blinker", Some(Periodicity::Oscillator), 2, 1, "xp2_7"),
+            ("glider", Some(Periodicity::Spaceship), 4, 1, "xq4_153"),
+            ("lightweight spaceship", Some(Periodicity::Spaceship), 4, 1, "xq4_6frc"),
+        ]);
+        assert_eq!(entries[0].rle, "2o$2o!");
+    }
+
+    #[test]
+    fn names_objects_of_other_rules_by_kind() {
+        let rule: Rule = "B36/S23".parse().unwrap();
+        let block = parse_cells("2o$2o!");
+        let entries = census(&rule, &block, 16);
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].name.as_str(), entries[0].apgcode.as_str()), ("still life", "xs4_33"));
+    }
+
+    #[test]
+    fn splits_objects_that_touch() {
+        // a bi-block is two blocks that never interact
+        let cells = parse_cells("2ob2o$2ob2o!");
+        let entries = census(&Rule::life(), &cells, 16);
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].name.as_str(), entries[0].count), ("block", 2));
+    }
+
+    #[test]
+    fn counts_every_phase_of_an_object_as_one() {
+        // some phases of spaceships and of the pulsar fall into parts that
+        // only interact three or four at a time
+        let rule = Rule::life();
+        for &(name, rle) in LIFE_OBJECTS {
+            let cells = parse_cells(rle);
+            let period = find_period(rule, Topology::default(), &cells, false, 100).unwrap();
+            for (phase, cells) in evolve(&rule, &cells, period.period - 1).iter().enumerate() {
+                let entries = census(&rule, cells, 100);
+                let summary: Vec<(&str, u64)> = entries.iter().map(|entry| (entry.name.as_str(), entry.count)).collect();
+                assert_eq!(summary, [(name, 1)], "phase {}", phase);
+            }
+        }
+    }
+
+    #[test]
+    fn splits_objects_that_pass_each_other() {
+        // a glider leaving a block behind, close enough to share neighbors
+        let cells = parse_cells("2o$2o2$3bo$4bo$2b3o!");
+        assert_eq!(interacting(&Rule::life(), &cells, 100).len(), 1);
+        let entries = census(&Rule::life(), &cells, 100);
+        let summary: Vec<(&str, u64)> = entries.iter().map(|entry| (entry.name.as_str(), entry.count)).collect();
+        assert_eq!(summary, [("block", 1), ("glider", 1)]);
+    }
+}
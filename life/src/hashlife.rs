@@ -0,0 +1,1261 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::algorithm::Algorithm;
+use crate::arena::{Arena, NodeId, Table};
+use crate::leaf::{self, LEAF_LEVEL};
+use crate::rule::Rule;
+use crate::stats::Stats;
+use crate::topology::Topology;
+
+// node ids are unique, so a key can only ever match the node it was made for.
+// The last field is the rule's index in `Hashlife::rules`, so successors under
+// one rule are still there after switching to another and back
+type SuccessorKey = (NodeId, Option<u8>, bool, u32);
+
+// stepping a node this big or bigger that isn't in the cache first checks the
+// memory limit, smaller ones are done before it could make a difference
+const LIMIT_LEVEL: u8 = LEAF_LEVEL + 3;
+
+// with the parallel feature, the sub-successors of nodes at least this big
+// are stepped side by side, smaller ones are not worth handing to a thread
+#[cfg(feature = "parallel")]
+const PARALLEL_LEVEL: u8 = 10;
+
+/// The quadtree engine, which remembers the successor of every node it has
+/// stepped, so repetition in space and time costs next to nothing. Each one
+/// owns its own nodes, root and caches.
+///
+/// Once a `B0` rule turns the background on, the cells in `root` are stored
+/// relative to it: a stored cell is alive where the visible cell differs from
+/// the background. `phases` holds the rule that steps the stored cells while
+/// the background is off and on.
+pub struct Hashlife {
+    nodes: Arena,
+    root: NodeId,
+    rule: Rule,
+    topology: Topology,
+    background: bool,
+    phases: [Rule; 2],
+    // every rule run since the last garbage collection, see `SuccessorKey`
+    rules: Vec<Rule>,
+    rule_id: u32,
+
+    zero_cache: HashMap<u8, NodeId>,
+    successor_cache: Table<SuccessorKey, NodeId>,
+    // the cached successors debug mode has stepped again, see `successor`
+    verified: Table<SuccessorKey, ()>,
+    call_count: AtomicUsize,
+    successor_hits: AtomicU64,
+    successor_misses: AtomicU64,
+    debug: bool,
+    collisions: AtomicU64,
+    memory_limit: usize,
+    // set when a step went over the memory limit part way, see `jump_power`
+    aborted: AtomicBool,
+    // how many times garbage was collected, which renumbers the nodes
+    collections: u64,
+
+    // roots that outlive edits and runs, see `keep`
+    kept: HashMap<u32, (NodeId, bool)>,
+    next_kept: u32,
+}
+
+/// A pattern put aside by `Hashlife::keep`, to be brought back with
+/// `restore` or dropped with `release`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Kept(u32);
+
+// the murmur3 finalizer, so that the hashes of parents are not linear in
+// those of their grandchildren
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51AFD7ED558CCD);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xC4CEB9FE1A85EC53);
+    hash ^ (hash >> 33)
+}
+
+// a hash of the cells of a node, which unlike its id is the same in every
+// universe
+fn content_hash(nodes: &Arena, node: NodeId, memo: &mut HashMap<NodeId, u64>) -> u64 {
+    if let Some(&hash) = memo.get(&node) {
+        return hash;
+    }
+    let n = &nodes[node];
+    let hash = if n.level == LEAF_LEVEL && nodes.is_decaying(node) {
+        nodes.cells(node).iter().fold(n.bits, |acc, &state| mix(acc ^ u64::from(state)))
+    }
+    else if n.level == LEAF_LEVEL {
+        mix(n.bits)
+    }
+    else {
+        let [a, b, c, d] = nodes.children(node).map(|child| content_hash(nodes, child, memo));
+        mix((u64::from(n.level) + 1)
+            .wrapping_add(a.wrapping_mul(2223243435546756677))
+            .wrapping_add(b.wrapping_mul(1241111124211111421))
+            .wrapping_add(c.wrapping_mul(7532753275327532753))
+            .wrapping_add(d.wrapping_mul(9876503214123056789)))
+    } & ((1 << 63) - 1);
+    memo.insert(node, hash);
+    hash
+}
+
+// the middle leaf of a 16x16 node after a generation of each rule, at most
+// 4, counting neighbors a row at a time unless some cells are decaying
+fn step_leaves(nodes: &Arena, rules: &[Rule], m: NodeId) -> NodeId {
+    let quadrants = nodes.children(m);
+    if rules[0].states() == 2 && !quadrants.iter().any(|&q| nodes.is_decaying(q)) {
+        let bits = leaf::step_bits(rules, quadrants.map(|q| nodes[q].bits));
+        nodes.leaf_bits(bits)
+    }
+    else {
+        let cells = leaf::step_cells(rules, leaf::grid(quadrants.map(|q| nodes.cells(q))));
+        nodes.leaf(&cells)
+    }
+}
+
+// the middle half of the node made of four nodes
+fn middle(nodes: &Arena, quadrants: [NodeId; 4]) -> NodeId {
+    let [a, b, c, d] = quadrants;
+    if nodes[a].level > LEAF_LEVEL {
+        return nodes.join(nodes[a].d, nodes[b].c, nodes[c].b, nodes[d].a);
+    }
+    if !quadrants.iter().any(|&q| nodes.is_decaying(q)) {
+        let bits = leaf::middle_bits(quadrants.map(|q| nodes[q].bits));
+        nodes.leaf_bits(bits)
+    }
+    else {
+        let cells = leaf::middle(&leaf::grid(quadrants.map(|q| nodes.cells(q))));
+        nodes.leaf(&cells)
+    }
+}
+
+// whether the square of 2^level cells at (x, y) from the top left of the node
+// is empty, in u128 so that nodes past level 64 fit
+fn is_empty(nodes: &Arena, node: NodeId, x: u128, y: u128, level: u8) -> bool {
+    let n = &nodes[node];
+    if n.population == 0 {
+        return true;
+    }
+    if n.level == level {
+        return false;
+    }
+    if n.level == LEAF_LEVEL {
+        let row = ((1 << (1 << level)) - 1) << x;
+        let square = (0..1 << level).fold(0, |acc, dy| acc | row << (8 * (y + dy)));
+        return n.bits & square == 0;
+    }
+
+    let half = 1_u128 << (n.level - 1);
+    let child = match (x >= half, y >= half) {
+        (false, false) => n.a,
+        (true, false) => n.b,
+        (false, true) => n.c,
+        (true, true) => n.d,
+    };
+    is_empty(nodes, child, x % half, y % half, level)
+}
+
+// whether every cell is in the middle quarter of the node, checked by emptiness
+// rather than by comparing populations, which could both be saturated: the
+// ring of squares an eighth of the node wide around the middle quarter and
+// the ring of squares a quarter wide around that have to be empty
+fn is_padded(nodes: &Arena, node: NodeId) -> bool {
+    let ring = |from: u128, level: u8| (from..from + 4).flat_map(move |y| (from..from + 4).map(move |x| (x, y)))
+        .filter(move |&(x, y)| x == from || x == from + 3 || y == from || y == from + 3)
+        .map(move |(x, y)| (x << level, y << level, level));
+
+    let level = nodes[node].level;
+    ring(0, level - 2).chain(ring(2, level - 3))
+        .all(|(x, y, level)| is_empty(nodes, node, x, y, level))
+}
+
+// adds two numbers stored as base 10^9 digits, least significant first
+fn add_digits(x: &mut Vec<u32>, y: &[u32]) {
+    let mut carry = 0;
+    for i in 0..x.len().max(y.len()) {
+        if i == x.len() {
+            x.push(0);
+        }
+        let sum = x[i] + y.get(i).copied().unwrap_or(0) + carry;
+        x[i] = sum % 1_000_000_000;
+        carry = sum / 1_000_000_000;
+    }
+    if carry > 0 {
+        x.push(carry);
+    }
+}
+
+// the population in base 10^9 digits, only descending into nodes whose u64
+// population saturated
+fn exact_population(nodes: &Arena, node: NodeId, memo: &mut HashMap<NodeId, Vec<u32>>) -> Vec<u32> {
+    let population = nodes[node].population;
+    if population < u64::MAX {
+        let mut digits = Vec::new();
+        add_digits(&mut digits, &[
+            (population % 1_000_000_000) as u32,
+            (population / 1_000_000_000 % 1_000_000_000) as u32,
+            (population / 1_000_000_000_000_000_000) as u32,
+        ]);
+        return digits;
+    }
+
+    if let Some(digits) = memo.get(&node) {
+        return digits.clone();
+    }
+    let mut digits = Vec::new();
+    for child in nodes.children(node) {
+        add_digits(&mut digits, &exact_population(nodes, child, memo));
+    }
+    memo.insert(node, digits.clone());
+    digits
+}
+
+fn inner(nodes: &Arena, node: NodeId) -> NodeId {
+    middle(nodes, nodes.children(node))
+}
+
+fn crop(nodes: &Arena, node: NodeId) -> NodeId {
+    if nodes[node].level <= LEAF_LEVEL || !is_padded(nodes, node) {
+        node
+    }
+    else {
+        let inner = inner(nodes, node);
+        crop(nodes, inner)
+    }
+}
+
+// the cells that are not in state 0 with their states, x and y being the top
+// left cell of the node, kept in i128 so that offsets inside nodes well past
+// level 64 still fit
+fn expand_recurse(nodes: &Arena, node: NodeId, x: i128, y: i128) -> Vec<(i128, i128, u8)> {
+    let n = &nodes[node];
+    if n.population == 0 {
+        return Vec::new()
+    }
+
+    if n.level == LEAF_LEVEL {
+        let cells = nodes.cells(node);
+        (0..64).filter(|&i| cells[i] != 0).map(|i| (x + i as i128 % 8, y + i as i128 / 8, cells[i])).collect()
+    }
+    else {
+        let offset = 1_i128 << (n.level - 1);
+        let mut output = Vec::new();
+        output.append(&mut expand_recurse(nodes, n.a, x, y));
+        output.append(&mut expand_recurse(nodes, n.b, x + offset, y));
+        output.append(&mut expand_recurse(nodes, n.c, x, y + offset));
+        output.append(&mut expand_recurse(nodes, n.d, x + offset, y + offset));
+        output
+    }
+}
+
+// like `expand_recurse`, but only the cells in the rectangle from (left, top)
+// up to (right, bottom), skipping every node outside it
+fn expand_rect(nodes: &Arena, node: NodeId, x: i128, y: i128, rect: (i128, i128, i128, i128)) -> Vec<(i128, i128, u8)> {
+    let n = &nodes[node];
+    let (left, top, right, bottom) = rect;
+    let size = 1_i128 << n.level;
+    if n.population == 0 || x >= right || y >= bottom || x + size <= left || y + size <= top {
+        return Vec::new()
+    }
+
+    if n.level == LEAF_LEVEL {
+        expand_recurse(nodes, node, x, y).into_iter()
+            .filter(|&(x, y, _)| x >= left && y >= top && x < right && y < bottom)
+            .collect()
+    }
+    else {
+        let offset = size >> 1;
+        let mut output = Vec::new();
+        output.append(&mut expand_rect(nodes, n.a, x, y, rect));
+        output.append(&mut expand_rect(nodes, n.b, x + offset, y, rect));
+        output.append(&mut expand_rect(nodes, n.c, x, y + offset, rect));
+        output.append(&mut expand_rect(nodes, n.d, x + offset, y + offset, rect));
+        output
+    }
+}
+
+// replaces the leaf holding the cell at (x, y), relative to the center of the
+// node, with what `update` makes of it and the index of the cell in it
+fn update_leaf(nodes: &Arena, node: NodeId, x: i128, y: i128, update: &dyn Fn(NodeId, usize) -> NodeId) -> NodeId {
+    if nodes[node].level == LEAF_LEVEL {
+        return update(node, ((y + 4) * 8 + x + 4) as usize)
+    }
+
+    let offset = (1_i128 << nodes[node].level) >> 2;
+    let [mut a, mut b, mut c, mut d] = nodes.children(node);
+
+    if x >= 0 && y >= 0 {
+        d = update_leaf(nodes, d, x - offset, y - offset, update);
+    }
+    else if x < 0 && y >= 0 {
+        c = update_leaf(nodes, c, x + offset, y - offset, update);
+    }
+    else if x >= 0 && y < 0 {
+        b = update_leaf(nodes, b, x - offset, y + offset, update);
+    }
+    else {
+        a = update_leaf(nodes, a, x + offset, y + offset, update);
+    }
+    nodes.join(a, b, c, d)
+}
+
+fn set_cell_recurse(nodes: &Arena, node: NodeId, x: i128, y: i128, state: u8) -> NodeId {
+    update_leaf(nodes, node, x, y, &|leaf, i| {
+        let mut cells = nodes.cells(leaf);
+        cells[i] = state;
+        nodes.leaf(&cells)
+    })
+}
+
+fn get_state_recurse(nodes: &Arena, node: NodeId, x: i128, y: i128) -> u8 {
+    let n = &nodes[node];
+    if n.level == LEAF_LEVEL {
+        nodes.cells(node)[((y + 4) * 8 + x + 4) as usize]
+    }
+    else {
+        let offset = (1_i128 << n.level) >> 2;
+
+        if x >= 0 && y >= 0 {
+            get_state_recurse(nodes, n.d, x - offset, y - offset)
+        }
+        else if x < 0 && y >= 0 {
+            get_state_recurse(nodes, n.c, x + offset, y - offset)
+        }
+        else if x >= 0 && y < 0 {
+            get_state_recurse(nodes, n.b, x - offset, y + offset)
+        }
+        else {
+            get_state_recurse(nodes, n.a, x + offset, y + offset)
+        }
+    }
+}
+
+// how far the first live column (or row, with `rows`) is from the left (or
+// top) edge of the node, or from the right (or bottom) edge with `reverse`
+fn get_bounds_recurse(nodes: &Arena, node: NodeId, rows: bool, reverse: bool) -> Option<i128> {
+    let n = &nodes[node];
+    if n.population == 0 {
+        return None
+    }
+    if n.level == LEAF_LEVEL {
+        let bits = n.bits;
+        let columns = (0..8).fold(0, |acc, y| acc | (bits >> (8 * y)) as u8);
+        let distance = match (rows, reverse) {
+            (false, false) => columns.trailing_zeros(),
+            (false, true) => columns.leading_zeros(),
+            (true, false) => bits.trailing_zeros() / 8,
+            (true, true) => bits.leading_zeros() / 8,
+        };
+        return Some(distance.into())
+    }
+
+    let (near, far) = match (rows, reverse) {
+        (false, false) => ([n.a, n.c], [n.b, n.d]),
+        (false, true) => ([n.b, n.d], [n.a, n.c]),
+        (true, false) => ([n.a, n.b], [n.c, n.d]),
+        (true, true) => ([n.c, n.d], [n.a, n.b]),
+    };
+    let closest = |half: [NodeId; 2]| half.iter().filter_map(|&n| get_bounds_recurse(nodes, n, rows, reverse)).min();
+
+    // any live cell in the near half is closer than those in the far half
+    closest(near).or_else(|| closest(far).map(|distance| distance + (1_i128 << (n.level - 1))))
+}
+
+impl Hashlife {
+    pub(crate) fn nodes(&self) -> &Arena {
+        &self.nodes
+    }
+
+    pub(crate) fn root(&self) -> NodeId {
+        self.root
+    }
+
+    fn in_bounds(&self, x: i64, y: i64) -> bool {
+        match self.topology.bounds() {
+            Some((left, top, width, height)) => {
+                let (left, top, right, bottom) = (i64::from(left), i64::from(top), i64::from(left + width), i64::from(top + height));
+                x >= left && y >= top && x < right && y < bottom
+            }
+            None => true,
+        }
+    }
+
+    // the cells of the root are those from -half to half - 1 on both axes
+    fn root_contains(&self, x: i64, y: i64) -> bool {
+        let half = 1_i128 << (self.nodes[self.root].level - 1);
+        (-half..half).contains(&i128::from(x)) && (-half..half).contains(&i128::from(y))
+    }
+
+    // grows the root until it holds the cell
+    fn grow_to(&mut self, x: i64, y: i64) {
+        while !self.root_contains(x, y) {
+            self.root = self.center(self.root);
+        }
+    }
+
+    fn get_zero(&mut self, k: u8) -> NodeId {
+        if let Some(&n) = self.zero_cache.get(&k) {
+            return n;
+        }
+
+        let n = if k == LEAF_LEVEL {
+            self.nodes.leaf_bits(0)
+        }
+        else {
+            let z = self.get_zero(k - 1);
+            self.nodes.join(z, z, z, z)
+        };
+        self.zero_cache.insert(k, n);
+        n
+    }
+
+    // `background` is whether the background is alive at the start of the step
+    fn successor(&self, m: NodeId, j: Option<u8>, background: bool) -> NodeId {
+        self.call_count.fetch_add(1, Ordering::Relaxed);
+
+        let level = self.nodes[m].level;
+        if self.nodes[m].population == 0 {
+            return self.nodes[m].a;
+        }
+
+        let key = (m, j, background, self.rule_id);
+        if let Some(s) = self.successor_cache.get(&key) {
+            self.successor_hits.fetch_add(1, Ordering::Relaxed);
+            if !self.debug || self.verified.get(&key).is_some() {
+                return s;
+            }
+
+            // debug mode steps each cached successor again once, from the
+            // cached successors of its children, which only comes out
+            // different when a key outlived the node it was made for
+            let fresh = self.step_node(m, j, background);
+            if self.aborted.load(Ordering::Relaxed) {
+                return m;
+            }
+            if fresh != s {
+                self.collisions.fetch_add(1, Ordering::Relaxed);
+                self.successor_cache.insert(key, fresh);
+            }
+            self.verified.insert(key, ());
+            return fresh;
+        }
+        self.successor_misses.fetch_add(1, Ordering::Relaxed);
+
+        // over the memory limit the whole step gives up, see `jump_power`, and
+        // whatever is returned on the way out is thrown away
+        if level >= LIMIT_LEVEL && j != Some(0) && self.memory_estimate() > self.memory_limit {
+            self.aborted.store(true, Ordering::Relaxed);
+        }
+        if self.aborted.load(Ordering::Relaxed) {
+            return m;
+        }
+
+        let s = self.step_node(m, j, background);
+        if self.aborted.load(Ordering::Relaxed) {
+            return m;
+        }
+        self.successor_cache.insert(key, s);
+        s
+    }
+
+    // the successor of a non-empty node, as `successor` but without looking
+    // in the cache for the node itself
+    fn step_node(&self, m: NodeId, j: Option<u8>, background: bool) -> NodeId {
+        let level = self.nodes[m].level;
+        if level == LEAF_LEVEL + 1 {
+            // each generation's rule, see `Rule::background_after`
+            let phases: [Rule; 4] = std::array::from_fn(|g| self.phases[usize::from(self.rule.background_after(background, g as u64))]);
+            let generations = 1 << j.map_or(2, |j| j.min(2));
+            step_leaves(&self.nodes, &phases[..generations], m)
+        }
+        else {
+            let nodes = &self.nodes;
+            let [a, b, c, d] = nodes.children(m);
+            let [_, ab, ac, ad] = nodes.children(a);
+            let [ba, _, bc, bd] = nodes.children(b);
+            let [ca, cb, _, cd] = nodes.children(c);
+            let [da, db, dc, _] = nodes.children(d);
+            let quadrants = [
+                a,
+                nodes.join(ab, ba, ad, bc),
+                b,
+                nodes.join(ac, ad, ca, cb),
+                nodes.join(ad, bc, cb, da),
+                nodes.join(bc, bd, da, db),
+                c,
+                nodes.join(cb, da, cd, dc),
+                d,
+            ];
+            let [c1, c2, c3, c4, c5, c6, c7, c8, c9] = self.successors(quadrants, j, background);
+            if self.aborted.load(Ordering::Relaxed) {
+                return m;
+            }
+
+            if j.unwrap() < level - 2 {
+                let nodes = &self.nodes;
+                let (s1, s2) = (middle(nodes, [c1, c2, c4, c5]), middle(nodes, [c2, c3, c5, c6]));
+                let (s3, s4) = (middle(nodes, [c4, c5, c7, c8]), middle(nodes, [c5, c6, c8, c9]));
+                nodes.join(s1, s2, s3, s4)
+            } else {
+                // the second half starts wherever the first half left the background
+                let b = self.rule.background_after(background, 1 << (level - 3));
+                let nodes = &self.nodes;
+                let quadrants = [
+                    nodes.join(c1, c2, c4, c5),
+                    nodes.join(c2, c3, c5, c6),
+                    nodes.join(c4, c5, c7, c8),
+                    nodes.join(c5, c6, c8, c9),
+                ];
+                let [s1, s2, s3, s4] = self.successors(quadrants, j, b);
+                if self.aborted.load(Ordering::Relaxed) {
+                    return m;
+                }
+                nodes.join(s1, s2, s3, s4)
+            }
+        }
+    }
+
+    // the successors of nodes that do not depend on each other, stepped on
+    // every core with the parallel feature
+    fn successors<const N: usize>(&self, quadrants: [NodeId; N], j: Option<u8>, background: bool) -> [NodeId; N] {
+        #[cfg(feature = "parallel")]
+        if self.nodes[quadrants[0]].level >= PARALLEL_LEVEL {
+            use rayon::prelude::*;
+            let successors: Vec<NodeId> = quadrants.par_iter().map(|&q| self.successor(q, j, background)).collect();
+            return successors.try_into().unwrap();
+        }
+        quadrants.map(|q| self.successor(q, j, background))
+    }
+
+    fn center(&mut self, m: NodeId) -> NodeId {
+        if self.nodes[m].level == LEAF_LEVEL {
+            let [a, b, c, d] = leaf::quadrants(&leaf::surround(&self.nodes.cells(m))).map(|cells| self.nodes.leaf(&cells));
+            return self.nodes.join(a, b, c, d);
+        }
+        let z = self.get_zero(self.nodes[m].level - 1);
+        let [a, b, c, d] = self.nodes.children(m);
+        let nodes = &self.nodes;
+        let quadrants = [
+            nodes.join(z, z, z, a),
+            nodes.join(z, z, b, z),
+            nodes.join(z, c, z, z),
+            nodes.join(d, z, z, z),
+        ];
+        nodes.join(quadrants[0], quadrants[1], quadrants[2], quadrants[3])
+    }
+
+    fn pad(&mut self, node: NodeId) -> NodeId {
+        if self.nodes[node].level <= LEAF_LEVEL || !is_padded(&self.nodes, node) {
+            let centered = self.center(node);
+            self.pad(centered)
+        }
+        else {
+            node
+        }
+    }
+
+    // grows the root until it holds every cell within `margin` of the grid
+    fn cover(&mut self, left: i32, top: i32, width: i32, height: i32, margin: i32) {
+        let (left, top, right, bottom) = (left - margin, top - margin, left + width - 1 + margin, top + height - 1 + margin);
+        self.grow_to(i64::from(left), i64::from(top));
+        self.grow_to(i64::from(right), i64::from(bottom));
+    }
+
+    // clears every cell of `node`, whose top left cell is (x, y), outside the
+    // grid, with x and y in i128 like the offsets of `expand_recurse`
+    fn clip(&mut self, node: NodeId, x: i128, y: i128, bounds: (i32, i32, i32, i32)) -> NodeId {
+        let (left, top, width, height) = bounds;
+        let level = self.nodes[node].level;
+        let size = 1_i128 << level;
+        let (left, top, right, bottom) = (i128::from(left), i128::from(top), i128::from(left + width), i128::from(top + height));
+
+        if self.nodes[node].population == 0 || (x >= left && y >= top && x + size <= right && y + size <= bottom) {
+            return node;
+        }
+        if x >= right || y >= bottom || x + size <= left || y + size <= top {
+            return self.get_zero(level);
+        }
+        if level == LEAF_LEVEL {
+            let mut cells = self.nodes.cells(node);
+            for (i, state) in cells.iter_mut().enumerate() {
+                let (cell_x, cell_y) = (x + i as i128 % 8, y + i as i128 / 8);
+                if cell_x < left || cell_y < top || cell_x >= right || cell_y >= bottom {
+                    *state = 0;
+                }
+            }
+            return self.nodes.leaf(&cells);
+        }
+
+        let half = size >> 1;
+        let [a, b, c, d] = self.nodes.children(node);
+        let quadrants = [
+            self.clip(a, x, y, bounds),
+            self.clip(b, x + half, y, bounds),
+            self.clip(c, x, y + half, bounds),
+            self.clip(d, x + half, y + half, bounds),
+        ];
+        self.nodes.join(quadrants[0], quadrants[1], quadrants[2], quadrants[3])
+    }
+
+    fn clip_root(&mut self) {
+        if let Some(bounds) = self.topology.bounds() {
+            let corner = -(1_i128 << (self.nodes[self.root].level - 1));
+            self.root = self.clip(self.root, corner, corner, bounds);
+        }
+    }
+
+    // sets every cell of `node`, whose top left cell is (x, y), in the
+    // rectangle from (left, top) up to (right, bottom) to `state`. Nodes that
+    // are the same and cut the same way by the rectangle are filled once, so a
+    // long strip through empty space costs next to nothing
+    fn fill(&mut self, node: NodeId, x: i128, y: i128, rect: (i128, i128, i128, i128), state: u8, memo: &mut HashMap<(NodeId, [i128; 4]), NodeId>) -> NodeId {
+        let (left, top, right, bottom) = rect;
+        let level = self.nodes[node].level;
+        let size = 1_i128 << level;
+        if x >= right || y >= bottom || x + size <= left || y + size <= top {
+            return node;
+        }
+        let key = (node, [left.max(x) - x, top.max(y) - y, right.min(x + size) - x, bottom.min(y + size) - y]);
+        if let Some(&filled) = memo.get(&key) {
+            return filled;
+        }
+        if level == LEAF_LEVEL {
+            let mut cells = self.nodes.cells(node);
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let (cell_x, cell_y) = (x + i as i128 % 8, y + i as i128 / 8);
+                if cell_x >= left && cell_y >= top && cell_x < right && cell_y < bottom {
+                    *cell = state;
+                }
+            }
+            let filled = self.nodes.leaf(&cells);
+            memo.insert(key, filled);
+            return filled;
+        }
+
+        let half = size >> 1;
+        let [a, b, c, d] = self.nodes.children(node);
+        let quadrants = [
+            self.fill(a, x, y, rect, state, memo),
+            self.fill(b, x + half, y, rect, state, memo),
+            self.fill(c, x, y + half, rect, state, memo),
+            self.fill(d, x + half, y + half, rect, state, memo),
+        ];
+        let filled = self.nodes.join(quadrants[0], quadrants[1], quadrants[2], quadrants[3]);
+        memo.insert(key, filled);
+        filled
+    }
+
+    // copies the cells along the edges of the grid into the ring of cells just
+    // outside it, so that they are neighbors of the cells at the opposite edge.
+    // `clip_root` left the ring empty, so only live edge cells are copied and
+    // empty edges cost nothing, however long they are
+    fn wrap_edges(&mut self) {
+        let (left, top, width, height) = match self.topology.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        self.cover(left, top, width, height, 1);
+        let corner = -(1_i128 << (self.nodes[self.root].level - 1));
+        let (l, t, r, b) = (i128::from(left), i128::from(top), i128::from(left) + i128::from(width), i128::from(top) + i128::from(height));
+
+        // only planes have dead edges, and cells beyond them are dead, which
+        // is stored as the background
+        if self.topology.wrap(-1, -1).is_none() {
+            if self.background {
+                for rect in [(l - 1, t - 1, r + 1, t), (l - 1, b, r + 1, b + 1), (l - 1, t, l, b), (r, t, r + 1, b)] {
+                    self.root = self.fill(self.root, corner, corner, rect, 1, &mut HashMap::new());
+                }
+            }
+            return;
+        }
+
+        let edges: Vec<(i32, i32, u8)> = [(l, t, r, t + 1), (l, b - 1, r, b), (l, t, l + 1, b), (r - 1, t, r, b)].into_iter()
+            .flat_map(|rect| expand_rect(&self.nodes, self.root, corner, corner, rect))
+            .map(|(x, y, state)| ((x - l) as i32, (y - t) as i32, state))
+            .collect();
+        for (x, y, state) in edges {
+            // the ring cells an edge cell could land on, twisted or not
+            let ring = [
+                (x, -1), (width - 1 - x, -1), (x, height), (width - 1 - x, height),
+                (-1, y), (-1, height - 1 - y), (width, y), (width, height - 1 - y),
+                (-1, -1), (width, -1), (-1, height), (width, height),
+            ];
+            for (ring_x, ring_y) in ring {
+                if self.topology.wrap(ring_x, ring_y) == Some((x, y)) {
+                    self.root = set_cell_recurse(&self.nodes, self.root, i128::from(left + ring_x), i128::from(top + ring_y), state);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn memory_estimate(&self) -> usize {
+        self.nodes.bytes() + self.successor_cache.bytes() + self.verified.bytes()
+    }
+
+    // forgets every successor and then every node that neither the root nor a
+    // kept root is made of, the roots themselves carry on where they left off
+    fn collect_garbage(&mut self) {
+        self.collections += 1;
+        self.successor_cache = Table::default();
+        self.verified = Table::default();
+        self.rules = vec![self.rule];
+        self.rule_id = 0;
+        self.zero_cache = HashMap::new();
+        let mut roots: Vec<NodeId> = std::iter::once(self.root).chain(self.kept.values().map(|kept| kept.0)).collect();
+        self.nodes.retain(&mut roots);
+        self.root = roots[0];
+        for (kept, &root) in self.kept.values_mut().zip(&roots[1..]) {
+            kept.0 = root;
+        }
+    }
+
+    // the root of a pattern given as (x, y, state), leaving the pattern as it
+    // is
+    fn build(&mut self, cells: &[(i64, i64, u8)]) -> NodeId {
+        let mut leaves: HashMap<(i64, i64), [u8; 64]> = HashMap::new();
+        for &(x, y, state) in cells {
+            leaves.entry((x.div_euclid(8), y.div_euclid(8))).or_insert([0; 64])[(y.rem_euclid(8) * 8 + x.rem_euclid(8)) as usize] = state;
+        }
+
+        let empty = self.get_zero(LEAF_LEVEL);
+        let root = std::mem::replace(&mut self.root, empty);
+        for ((x, y), cells) in leaves {
+            let (x, y) = (x * 8, y * 8);
+            self.grow_to(x, y);
+            self.grow_to(x + 7, y + 7);
+            let leaf = self.nodes.leaf(&cells);
+            self.root = update_leaf(&self.nodes, self.root, i128::from(x), i128::from(y), &|_, _| leaf);
+        }
+        std::mem::replace(&mut self.root, root)
+    }
+
+    // steps n generations by splitting n into powers of two that one
+    // successor can jump
+    fn jump(&mut self, mut n: u64) {
+        let mut bits = Vec::new();
+        while n > 0 {
+            bits.push(n & 1);
+            n >>= 1;
+            self.root = self.center(self.root);
+        }
+
+        for (k, bit) in bits.iter().rev().enumerate() {
+            let j: u8 = (bits.len() - k - 1).try_into().unwrap();
+            if bit != &0 {
+                self.jump_power(j);
+            }
+        }
+
+        self.root = crop(&self.nodes, self.root);
+    }
+
+    // collects garbage, and when the roots and kept roots alone are still over
+    // the memory limit, raises it to four times what they take up, room for
+    // the tables to double at least once, or every step from then on would
+    // give up and collect garbage again
+    fn make_room(&mut self) {
+        self.collect_garbage();
+        let estimate = self.memory_estimate();
+        if estimate > self.memory_limit {
+            self.memory_limit = estimate.saturating_mul(4);
+        }
+    }
+
+    // steps 2^j generations with one successor. When that goes over the
+    // memory limit part way, like Golly it collects garbage and starts over,
+    // in two halves, since the whole step would most likely fill the memory
+    // again. Single generations never give up
+    fn jump_power(&mut self, j: u8) {
+        if self.memory_estimate() > self.memory_limit {
+            self.make_room();
+        }
+        let padded = self.pad(self.root);
+        let s = self.successor(padded, Some(j), self.background);
+        if self.aborted.swap(false, Ordering::Relaxed) {
+            self.make_room();
+            self.jump_power(j - 1);
+            self.jump_power(j - 1);
+            return;
+        }
+        self.root = s;
+        self.background = self.rule.background_after(self.background, 1 << j);
+    }
+}
+
+
+impl Default for Hashlife {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hashlife {
+    pub fn new() -> Hashlife {
+        let nodes = Arena::default();
+        let root = nodes.leaf_bits(0);
+        Hashlife {
+            nodes,
+            root,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            background: false,
+            phases: [Rule::default().emulate(false), Rule::default().emulate(true)],
+            rules: vec![Rule::default()],
+            rule_id: 0,
+
+            zero_cache: HashMap::new(),
+            successor_cache: Table::default(),
+            verified: Table::default(),
+            call_count: AtomicUsize::new(0),
+            successor_hits: AtomicU64::new(0),
+            successor_misses: AtomicU64::new(0),
+            debug: false,
+            collisions: AtomicU64::new(0),
+            memory_limit: 256 << 20,
+            aborted: AtomicBool::new(false),
+            collections: 0,
+
+            kept: HashMap::new(),
+            next_kept: 0,
+        }
+    }
+
+    pub fn hash(&self) -> u64 { content_hash(&self.nodes, self.root, &mut HashMap::new()) }
+
+    /// The exact population in decimal, or `Infinity` while an infinite
+    /// background is alive.
+    pub fn population_string(&self) -> String {
+        if self.background && !self.topology.is_bounded() {
+            return "Infinity".to_string();
+        }
+        if self.background || self.nodes[self.root].population < u64::MAX {
+            return self.population().to_string();
+        }
+
+        let digits = exact_population(&self.nodes, self.root, &mut HashMap::new());
+        let mut output = digits.last().unwrap().to_string();
+        for digit in digits.iter().rev().skip(1) {
+            output.push_str(&format!("{:09}", digit));
+        }
+        output
+    }
+
+    /// Caps the memory used by nodes and cached successors, in bytes. It is
+    /// raised when the patterns alone don't fit under it.
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.memory_limit = bytes;
+    }
+
+    pub fn memory_limit(&self) -> usize { self.memory_limit }
+
+    /// In debug mode every successor found in the cache is stepped again the
+    /// first time it is found, and compared, see `collisions`.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        self.collisions.store(0, Ordering::Relaxed);
+    }
+
+    /// How many cached successors came out different when stepped again since
+    /// debug mode was turned on. The new ones replace them.
+    pub fn collisions(&self) -> u64 { self.collisions.load(Ordering::Relaxed) }
+
+    pub fn level(&self) -> u8 { self.nodes[self.root].level }
+
+    /// How many successors were not in the cache, ever.
+    pub fn successor_misses(&self) -> u64 { self.successor_misses.load(Ordering::Relaxed) }
+
+    pub(crate) fn stats(&self) -> Stats {
+        Stats {
+            live_nodes: self.nodes.len(),
+            successor_cache: self.successor_cache.len(),
+            zero_cache: self.zero_cache.len(),
+            join_cache: self.nodes.joins(),
+
+            successor_hits: self.successor_hits.load(Ordering::Relaxed),
+            successor_misses: self.successor_misses.load(Ordering::Relaxed),
+            join_hits: self.nodes.hits(),
+            join_misses: self.nodes.misses(),
+
+            successor_calls: self.call_count.load(Ordering::Relaxed),
+            bytes: self.memory_estimate(),
+            ..Stats::default()
+        }
+    }
+
+    /// Puts the pattern aside, keeping its nodes through garbage collection
+    /// until it is restored or released.
+    pub fn keep(&mut self) -> Kept {
+        self.next_kept += 1;
+        self.kept.insert(self.next_kept, (self.root, self.background));
+        Kept(self.next_kept)
+    }
+
+    /// Brings back a pattern put aside by `keep`, which stays kept. The rule
+    /// is left as it is.
+    pub fn restore(&mut self, kept: &Kept) {
+        (self.root, self.background) = self.kept[&kept.0];
+    }
+
+    pub fn release(&mut self, kept: Kept) {
+        self.kept.remove(&kept.0);
+    }
+
+    /// Roughly the memory that the nodes of kept patterns take up.
+    pub fn kept_bytes<'a>(&self, kept: impl Iterator<Item = &'a Kept>) -> usize {
+        let roots: Vec<NodeId> = kept.map(|kept| self.kept[&kept.0].0).collect();
+        self.nodes.bytes_of(&roots)
+    }
+
+    /// A hash of the pattern that stays the same when it moves, and the top
+    /// left corner of its bounds, or `None` when it is empty. On bounded grids
+    /// the pattern is hashed where it is, with the corner at the origin.
+    pub fn shape(&mut self) -> Option<(u64, [i64; 2])> {
+        let [min_x, _, min_y, _] = self.bounds()?;
+        let (x, y) = if self.topology.is_bounded() { (0, 0) } else { (min_x, min_y) };
+
+        // the root is rebuilt even where the pattern stays put, so that the
+        // padding left by earlier steps doesn't change the hash
+        let cells: Vec<(i64, i64, u8)> = self.cells().into_iter()
+            .map(|(cell_x, cell_y, state)| (cell_x - x, cell_y - y, state))
+            .collect();
+        let root = self.build(&cells);
+        let root = crop(&self.nodes, root);
+        Some((content_hash(&self.nodes, root, &mut HashMap::new()), [x, y]))
+    }
+
+    /// Forgets the pattern along with every node and successor that no kept
+    /// pattern needs.
+    pub fn clear(&mut self) {
+        self.load(&[], false);
+        self.collect_garbage();
+    }
+
+    /// Replaces the pattern with cells given as `[x, y]` pairs and their
+    /// states, placed at the middle of a bounded grid.
+    pub fn construct_states(&mut self, pts: &[i64], states: &[u8]) {
+        if pts.is_empty() || pts.len() % 2 == 1 || states.len() != pts.len() / 2 { return }
+
+        let x_vals: Vec::<i64> = pts.chunks(2).map(|c| c[0]).collect();
+        let y_vals: Vec::<i64> = pts.chunks(2).map(|c| c[1]).collect();
+        let min_x = x_vals.iter().min().unwrap();
+        let min_y = y_vals.iter().min().unwrap();
+        let max_x = x_vals.iter().max().unwrap();
+        let max_y = y_vals.iter().max().unwrap();
+
+        // patterns smaller than a leaf start where they would in the smallest
+        // square holding them, at least 2x2, once that square is centered
+        let (span_x, span_y) = (max_x.abs_diff(*min_x), max_y.abs_diff(*min_y));
+        let span = span_x.max(span_y);
+        let level = (u64::BITS - span.leading_zeros()).max(1);
+        let shift = if level < LEAF_LEVEL.into() { (1 << (LEAF_LEVEL - 1)) - (1 << (level - 1)) } else { 0 };
+
+        // the cells of each leaf, keyed by the leaf's position. Offsets from
+        // the top left cell are unsigned, since a pattern can span all of i64
+        let mut leaves: HashMap<(i64, i64), [u8; 64]> = HashMap::new();
+        for n in 0..x_vals.len() {
+            let (x, y) = (x_vals[n].abs_diff(*min_x) + shift, y_vals[n].abs_diff(*min_y) + shift);
+            leaves.entry(((x >> 3) as i64, (y >> 3) as i64)).or_insert([0; 64])[(y % 8 * 8 + x % 8) as usize] = states[n];
+        }
+        let mut pattern: HashMap<(i64, i64), NodeId> = leaves.iter()
+            .map(|(&position, cells)| (position, self.nodes.leaf(cells)))
+            .collect();
+
+        let mut k = LEAF_LEVEL;
+        let mut last_updated = (0, 0);
+        while pattern.len() != 1 {
+            let mut next_level = std::collections::HashMap::new();
+            let z = self.get_zero(k);
+
+            while !pattern.is_empty() {
+                let (mut x, mut y) = pattern.iter().next().unwrap().0;
+                x -= x & 1;
+                y -= y & 1;
+
+                let a = pattern.remove(&(x, y)).unwrap_or(z);
+                let b = pattern.remove(&(x + 1, y)).unwrap_or(z);
+                let c = pattern.remove(&(x, y + 1)).unwrap_or(z);
+                let d = pattern.remove(&(x + 1, y + 1)).unwrap_or(z);
+
+                last_updated = (x >> 1, y >> 1);
+                next_level.insert((x >> 1, y >> 1), self.nodes.join(a, b, c, d));
+
+            }
+            pattern = next_level;
+            k += 1;
+        }
+        self.root = self.pad(pattern[&last_updated]);
+        self.background = false;
+
+        // bounded grids get the pattern in their middle
+        if let Some((left, top, width, height)) = self.topology.bounds() {
+            let offset_x = i128::from(left) + (i128::from(width) - i128::from(span_x) - 1) / 2;
+            let offset_y = i128::from(top) + (i128::from(height) - i128::from(span_y) - 1) / 2;
+
+            self.root = self.get_zero(LEAF_LEVEL);
+            self.cover(left, top, width, height, 0);
+            for n in 0..x_vals.len() {
+                let (x, y) = (i128::from(x_vals[n].abs_diff(*min_x)) + offset_x, i128::from(y_vals[n].abs_diff(*min_y)) + offset_y);
+                let (Ok(x), Ok(y)) = (i64::try_from(x), i64::try_from(y)) else { continue };
+                if !self.in_bounds(x, y) { continue }
+                self.root = set_cell_recurse(&self.nodes, self.root, i128::from(x), i128::from(y), states[n]);
+            }
+            self.clip_root();
+        }
+    }
+}
+
+impl Algorithm for Hashlife {
+    fn set_rule(&mut self, rule: Rule, topology: Topology) -> Result<(), String> {
+        if self.background && rule.states() > 2 {
+            return Err("Generations rules need the background to be dead".to_string());
+        }
+        self.rule_id = match self.rules.iter().position(|&r| r == rule) {
+            Some(id) => id as u32,
+            None => {
+                self.rules.push(rule);
+                self.rules.len() as u32 - 1
+            }
+        };
+        self.rule = rule;
+        self.phases = [rule.emulate(false), rule.emulate(true)];
+        self.topology = topology;
+        self.clip_root();
+        Ok(())
+    }
+
+    fn load(&mut self, cells: &[(i64, i64, u8)], background: bool) {
+        self.root = self.build(cells);
+        self.background = background;
+        self.clip_root();
+    }
+
+    fn cells(&self) -> Vec<(i64, i64, u8)> {
+        let corner = -(1_i128 << (self.nodes[self.root].level - 1));
+        expand_recurse(&self.nodes, self.root, corner, corner).into_iter()
+            .map(|(x, y, state)| (x as i64, y as i64, state))
+            .collect()
+    }
+
+    fn background(&self) -> bool { self.background }
+
+    fn step(&mut self, n: u64) {
+        self.call_count.store(0, Ordering::Relaxed);
+
+        if self.topology.is_bounded() {
+            // the edges have to be joined again after every generation, but a
+            // bounded grid only holds so many patterns, so once one comes back
+            // the rest of the run skips every whole cycle
+            let mut seen: HashMap<(NodeId, bool), u64> = HashMap::new();
+            let mut collections = self.collections;
+            let mut generation = 0;
+            while generation < n {
+                if collections != self.collections {
+                    seen.clear();
+                    collections = self.collections;
+                }
+                let pattern = (crop(&self.nodes, self.root), self.background);
+                if let Some(start) = seen.insert(pattern, generation) {
+                    generation = n - (n - generation) % (generation - start);
+                    seen.clear();
+                    continue;
+                }
+
+                self.wrap_edges();
+                self.jump(1);
+                self.clip_root();
+                generation += 1;
+            }
+        }
+        else {
+            self.jump(n);
+        }
+    }
+
+    fn get_cell(&self, x: i64, y: i64) -> u8 {
+        if !self.in_bounds(x, y) { return 0 }
+        if !self.root_contains(x, y) { return u8::from(self.background) }
+        get_state_recurse(&self.nodes, self.root, i128::from(x), i128::from(y)) ^ u8::from(self.background)
+    }
+
+    fn set_cell(&mut self, x: i64, y: i64, state: u8) {
+        if !self.in_bounds(x, y) { return }
+        if let Some((left, top, width, height)) = self.topology.bounds() {
+            self.cover(left, top, width, height, 0);
+        }
+        self.grow_to(x, y);
+        self.root = set_cell_recurse(&self.nodes, self.root, i128::from(x), i128::from(y), state ^ u8::from(self.background));
+    }
+
+    fn bounds(&self) -> Option<[i64; 4]> {
+        if self.nodes[self.root].population == 0 {
+            return None;
+        }
+        let size = 1_i128 << self.nodes[self.root].level;
+        let corner = -(size >> 1);
+        let bound = |rows, reverse| get_bounds_recurse(&self.nodes, self.root, rows, reverse).unwrap();
+
+        Some([
+            corner + bound(false, false),
+            corner + size - 1 - bound(false, true),
+            corner + bound(true, false),
+            corner + size - 1 - bound(true, true),
+        ].map(|c| c.clamp(i64::MIN.into(), i64::MAX.into()) as i64))
+    }
+
+    fn population(&self) -> u64 {
+        match (self.background, self.topology.bounds()) {
+            (false, _) => self.nodes[self.root].population,
+            (true, None) => u64::MAX,
+            (true, Some((_, _, width, height))) => width as u64 * height as u64 - self.nodes[self.root].population,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R_PENTOMINO: [(i64, i64, u8); 5] = [(1, 0, 1), (2, 0, 1), (0, 1, 1), (1, 1, 1), (1, 2, 1)];
+
+    #[test]
+    fn collects_garbage_under_a_small_memory_limit() {
+        let (mut limited, mut unlimited) = (Hashlife::new(), Hashlife::new());
+        limited.set_memory_limit(256 << 10);
+        limited.load(&R_PENTOMINO, false);
+        unlimited.load(&R_PENTOMINO, false);
+
+        // the r-pentomino settles after 1103 generations
+        for n in [1, 100, 1000, 1 << 12] {
+            limited.step(n);
+            unlimited.step(n);
+            assert_eq!(limited.hash(), unlimited.hash(), "{}", n);
+        }
+        assert_eq!(limited.population(), 116);
+        assert!(limited.collections > 1, "{}", limited.collections);
+        assert!(limited.memory_estimate() < unlimited.memory_estimate());
+        assert_eq!(limited.memory_limit(), 256 << 10);
+    }
+
+    #[test]
+    fn raises_a_memory_limit_the_roots_alone_go_over() {
+        const GLIDER: [(i64, i64, u8); 5] = [(1, 0, 1), (2, 1, 1), (0, 2, 1), (1, 2, 1), (2, 2, 1)];
+        let (mut limited, mut unlimited) = (Hashlife::new(), Hashlife::new());
+        let big: Vec<(i64, i64, u8)> = (0..256).flat_map(|y| (0..256).map(move |x| (x, y, 1)))
+            .filter(|&(x, y, _)| (x * x + 3 * y + x * y) % 7 == 0)
+            .collect();
+        limited.load(&big, false);
+        let _kept = limited.keep();
+        limited.collect_garbage();
+        let kept_bytes = limited.memory_estimate();
+        limited.set_memory_limit(kept_bytes / 2);
+
+        // with the limit under what the kept root takes, every step would
+        // give up and halve, down to single generations
+        limited.load(&GLIDER, false);
+        unlimited.load(&GLIDER, false);
+        limited.step(1 << 12);
+        unlimited.step(1 << 12);
+        let sorted = |hashlife: &Hashlife| {
+            let mut cells = hashlife.cells();
+            cells.sort();
+            cells
+        };
+        assert_eq!(sorted(&limited), sorted(&unlimited));
+        assert!(limited.memory_limit() > kept_bytes);
+        assert!(limited.collections < 10, "{}", limited.collections);
+    }
+
+    #[test]
+    fn steps_cached_successors_again_in_debug_mode() {
+        let mut hashlife = Hashlife::new();
+        hashlife.load(&R_PENTOMINO, false);
+        let m = hashlife.pad(hashlife.root);
+        let s = hashlife.successor(m, Some(1), false);
+
+        // a key that outlived its node hands out another node's successor
+        let wrong = hashlife.get_zero(hashlife.nodes[s].level);
+        hashlife.successor_cache.insert((m, Some(1), false, hashlife.rule_id), wrong);
+        assert_eq!(hashlife.successor(m, Some(1), false), wrong);
+
+        hashlife.set_debug(true);
+        assert_eq!(hashlife.successor(m, Some(1), false), s);
+        assert_eq!(hashlife.successor(m, Some(1), false), s);
+        assert_eq!(hashlife.collisions(), 1);
+
+        // a run with nothing wrong in the cache finds nothing
+        let (mut debug, mut plain) = (Hashlife::new(), Hashlife::new());
+        debug.set_debug(true);
+        for hashlife in [&mut debug, &mut plain] {
+            hashlife.load(&R_PENTOMINO, false);
+            for n in [1, 100, 1000, 1 << 12] {
+                hashlife.step(n);
+            }
+        }
+        assert_eq!((debug.hash(), debug.collisions()), (plain.hash(), 0));
+    }
+
+    #[test]
+    fn keeps_successors_across_rule_changes() {
+        let mut hashlife = Hashlife::new();
+        hashlife.load(&R_PENTOMINO, false);
+        hashlife.step(64);
+        let (hash, misses) = (hashlife.hash(), hashlife.successor_misses.load(Ordering::Relaxed));
+
+        // highlife steps the same nodes to different successors
+        hashlife.set_rule("B36/S23".parse().unwrap(), Topology::default()).unwrap();
+        hashlife.load(&R_PENTOMINO, false);
+        hashlife.step(64);
+        assert_ne!(hashlife.hash(), hash);
+
+        hashlife.set_rule(Rule::default(), Topology::default()).unwrap();
+        let misses_before = hashlife.successor_misses.load(Ordering::Relaxed);
+        hashlife.load(&R_PENTOMINO, false);
+        hashlife.step(64);
+        assert_eq!(hashlife.hash(), hash);
+        assert_eq!(hashlife.successor_misses.load(Ordering::Relaxed), misses_before);
+        assert!(misses_before > misses);
+    }
+
+    #[test]
+    fn counts_populations_past_u64_exactly() {
+        let mut hashlife = Hashlife::new();
+        hashlife.load(&R_PENTOMINO, false);
+        assert_eq!((hashlife.population(), hashlife.population_string()), (5, "5".to_string()));
+
+        // every cell of a node 2^36 cells wide is alive
+        let mut node = hashlife.nodes.leaf_bits(u64::MAX);
+        while hashlife.nodes[node].level < 36 {
+            node = hashlife.nodes.join(node, node, node, node);
+        }
+        let empty = hashlife.get_zero(36);
+        hashlife.root = hashlife.nodes.join(node, empty, empty, empty);
+        assert_eq!(hashlife.population(), u64::MAX);
+        assert_eq!(hashlife.population_string(), (1_u128 << 72).to_string());
+    }
+
+    // with the parallel feature the sub-successors of big nodes are stepped on
+    // every core, which has to give the same cells as stepping one at a time
+    #[test]
+    fn steps_big_nodes_like_quicklife() {
+        let soup: Vec<(i64, i64, u8)> = crate::soup::random(0x2545F4914F6CDD1D).zip(0..64 * 64)
+            .filter_map(|(seed, i)| (seed & 1 == 1).then_some((i % 64, i / 64, 1)))
+            .collect();
+        let mut hashlife = Hashlife::new();
+        let mut quicklife = crate::quicklife::QuickLife::new();
+        hashlife.load(&soup, false);
+        quicklife.load(&soup, false);
+
+        for n in [1, 255, 768] {
+            hashlife.step(n);
+            quicklife.step(n);
+            let (mut stepped, mut jumped) = (quicklife.cells(), hashlife.cells());
+            stepped.sort();
+            jumped.sort();
+            assert_eq!(jumped, stepped, "{}", n);
+        }
+        #[cfg(feature = "parallel")]
+        assert!(hashlife.level() >= PARALLEL_LEVEL);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+
+use crate::hashlife::Kept;
+use crate::quicklife::QuickLife;
+use crate::rule::Rule;
+use crate::topology::Topology;
+
+/// Everything an edit or a run can change about a universe. A hashlife
+/// pattern is just its root, kept alive in the arena, so taking one costs
+/// next to nothing whatever the size of the pattern.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub pattern: Pattern,
+    pub generation: u64,
+    pub rule: Rule,
+    pub topology: Topology,
+    // which run of the pattern between edits it was taken in, see `Universe`
+    pub course: u64,
+}
+
+/// The engine that ran a snapshot and its cells.
+#[derive(Debug)]
+pub enum Pattern {
+    Hashlife(Kept),
+    QuickLife(Box<QuickLife>),
+}
+
+/// The snapshots to go back to with undo and forward again with redo, at
+/// most `depth` of each. Recording a new one forgets everything that could
+/// be redone.
+///
+/// Snapshots that fall out are handed back, so that their roots can be
+/// released.
+#[derive(Debug)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    depth: usize,
+}
+
+impl History {
+    pub fn new(depth: usize) -> History {
+        History { undo: VecDeque::new(), redo: Vec::new(), depth }
+    }
+
+    pub fn depth(&self) -> usize { self.depth }
+
+    pub fn can_undo(&self) -> bool { !self.undo.is_empty() }
+
+    pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+    /// Keeps at most `depth` snapshots each way, dropping the oldest.
+    pub fn set_depth(&mut self, depth: usize) -> Vec<Snapshot> {
+        self.depth = depth;
+        let mut dropped: Vec<Snapshot> = self.undo.drain(..self.undo.len().saturating_sub(depth)).collect();
+        dropped.extend(self.redo.drain(..self.redo.len().saturating_sub(depth)));
+        dropped
+    }
+
+    /// Records the state before an edit.
+    pub fn push(&mut self, snapshot: Snapshot) -> Vec<Snapshot> {
+        let mut dropped = std::mem::take(&mut self.redo);
+        self.undo.push_back(snapshot);
+        dropped.extend(self.set_depth(self.depth));
+        dropped
+    }
+
+    /// Swaps the current state for the last one recorded. Check `can_undo`
+    /// first.
+    pub fn undo(&mut self, current: Snapshot) -> Snapshot {
+        let snapshot = self.undo.pop_back().expect("nothing to undo");
+        self.redo.push(current);
+        snapshot
+    }
+
+    /// Swaps the current state for the last one undone. Check `can_redo`
+    /// first.
+    pub fn redo(&mut self, current: Snapshot) -> Snapshot {
+        let snapshot = self.redo.pop().expect("nothing to redo");
+        self.undo.push_back(current);
+        snapshot
+    }
+}
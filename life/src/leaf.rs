@@ -0,0 +1,229 @@
+use crate::rule::Rule;
+
+/// The level of the leaves of the tree, blocks of 8x8 cells.
+///
+/// A leaf packs its cells into a `u64` whose bit `8 * y + x` is the cell in
+/// row `y` and column `x`, set when the cell is not dead. Leaves with
+/// decaying cells of a Generations rule also keep the state of every cell.
+pub const LEAF_LEVEL: u8 = 3;
+
+// the rows of the 16x16 block made of four leaves, bit x of row y being the
+// cell in column x
+fn rows(quadrants: [u64; 4]) -> [u32; 16] {
+    let mut rows = [0; 16];
+    for y in 0..8 {
+        let byte = |bits: u64| (bits >> (8 * y) & 0xFF) as u32;
+        rows[y] = byte(quadrants[0]) | byte(quadrants[1]) << 8;
+        rows[y + 8] = byte(quadrants[2]) | byte(quadrants[3]) << 8;
+    }
+    rows
+}
+
+// the middle 8x8 cells of a 16x16 block
+fn middle_rows(rows: &[u32; 16]) -> u64 {
+    (0..8).fold(0, |acc, y| acc | u64::from(rows[y + 4] >> 4 & 0xFF) << (8 * y))
+}
+
+// one generation of every cell of a 16x16 block but those on its edges, which
+// would need cells from outside the block
+fn generation(rule: &Rule, rows: &[u32; 16]) -> [u32; 16] {
+    let mut next = [0; 16];
+    for y in 1..15 {
+        // bit x of block[i] is neighbor i of cell x, in the order of the rule's bits
+        let (above, row, below) = (rows[y - 1], rows[y], rows[y + 1]);
+        let block = [above << 1, above, above >> 1, row << 1, row, row >> 1, below << 1, below, below >> 1];
+
+        next[y] = match rule.counts() {
+            Some((birth, survival)) => {
+                // adds up the neighbors of every cell of the row at once, into
+                // the four bits of the count
+                let mut sum = [0; 4];
+                for (_, &neighbors) in block.iter().enumerate().filter(|&(i, _)| rule.neighbors() & 1 << i != 0) {
+                    let mut carry = neighbors;
+                    for digit in sum.iter_mut() {
+                        let overflow = *digit & carry;
+                        *digit ^= carry;
+                        carry = overflow;
+                    }
+                }
+                (0..9).fold(0, |acc, count| {
+                    let equal = sum.iter().enumerate()
+                        .fold(!0, |acc, (i, &digit)| acc & if count >> i & 1 != 0 { digit } else { !digit });
+                    let born = if birth >> count & 1 != 0 { !row } else { 0 };
+                    let survives = if survival >> count & 1 != 0 { row } else { 0 };
+                    acc | equal & (born | survives)
+                })
+            }
+            None => (1..15).filter(|&x| {
+                let neighborhood = (0..9).fold(0, |acc, i| acc | (block[i] >> x & 1) << i);
+                rule.next_state((row >> x & 1) as u8, neighborhood as usize) == 1
+            }).fold(0, |acc, x| acc | 1 << x),
+        } & 0x7FFE;
+    }
+    next
+}
+
+/// The middle leaf of four leaves of a two state rule after a generation of
+/// each of `rules`, at most 4.
+pub fn step_bits(rules: &[Rule], quadrants: [u64; 4]) -> u64 {
+    let mut rows = rows(quadrants);
+    for rule in rules {
+        rows = generation(rule, &rows);
+    }
+    middle_rows(&rows)
+}
+
+/// The 16x16 cells of four leaves, in reading order.
+pub fn grid(quadrants: [[u8; 64]; 4]) -> [u8; 256] {
+    let mut grid = [0; 256];
+    for (q, cells) in quadrants.iter().enumerate() {
+        let (left, top) = (q % 2 * 8, q / 2 * 8);
+        for (i, &state) in cells.iter().enumerate() {
+            grid[(top + i / 8) * 16 + left + i % 8] = state;
+        }
+    }
+    grid
+}
+
+/// Splits 16x16 cells back into four leaves.
+pub fn quadrants(grid: &[u8; 256]) -> [[u8; 64]; 4] {
+    std::array::from_fn(|q| {
+        let (left, top) = (q % 2 * 8, q / 2 * 8);
+        std::array::from_fn(|i| grid[(top + i / 8) * 16 + left + i % 8])
+    })
+}
+
+/// The middle 8x8 cells of 16x16 cells.
+pub fn middle(grid: &[u8; 256]) -> [u8; 64] {
+    std::array::from_fn(|i| grid[(4 + i / 8) * 16 + 4 + i % 8])
+}
+
+/// 16x16 cells with `cells` in the middle.
+pub fn surround(cells: &[u8; 64]) -> [u8; 256] {
+    let mut grid = [0; 256];
+    for (i, &state) in cells.iter().enumerate() {
+        grid[(4 + i / 8) * 16 + 4 + i % 8] = state;
+    }
+    grid
+}
+
+/// Like `step_bits`, for cells in any state.
+pub fn step_cells(rules: &[Rule], mut grid: [u8; 256]) -> [u8; 64] {
+    for rule in rules {
+        let mut next = [0; 256];
+        for y in 1..15 {
+            for x in 1..15 {
+                let neighborhood = (0..9)
+                    .filter(|i| grid[(y + i / 3 - 1) * 16 + x + i % 3 - 1] == 1)
+                    .fold(0, |acc, i| acc | 1 << i);
+                next[y * 16 + x] = rule.next_state(grid[y * 16 + x], neighborhood);
+            }
+        }
+        grid = next;
+    }
+    middle(&grid)
+}
+
+/// The middle leaf of four leaves of a two state rule.
+pub fn middle_bits(quadrants: [u64; 4]) -> u64 {
+    middle_rows(&rows(quadrants))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a few generations of 16x16 cells by counting the neighbors of each cell,
+    // with dead cells all around
+    fn naive(birth: &[u32], survival: &[u32], offsets: &[(i64, i64)], states: u8, mut grid: [u8; 256], generations: usize) -> [u8; 64] {
+        for _ in 0..generations {
+            grid = std::array::from_fn(|i| {
+                let (x, y) = ((i % 16) as i64, (i / 16) as i64);
+                let count = offsets.iter()
+                    .filter(|&&(dx, dy)| (0..16).contains(&(x + dx)) && (0..16).contains(&(y + dy)))
+                    .filter(|&&(dx, dy)| grid[((y + dy) * 16 + x + dx) as usize] == 1)
+                    .count() as u32;
+                match grid[i] {
+                    0 if birth.contains(&count) => 1,
+                    1 if survival.contains(&count) => 1,
+                    0 => 0,
+                    state if state + 1 < states => state + 1,
+                    _ => 0,
+                }
+            });
+        }
+        middle(&grid)
+    }
+
+    fn soups(states: u8) -> impl Iterator<Item = [u8; 256]> {
+        let mut random = crate::soup::random(0x2545F4914F6CDD1D);
+        (0..200).map(move |_| std::array::from_fn(|_| {
+            let seed = random.next().unwrap();
+            if seed >> 62 == 0 { (seed >> 8) as u8 % (states - 1) + 1 } else { 0 }
+        }))
+    }
+
+    fn bits(cells: &[u8; 64]) -> u64 {
+        cells.iter().enumerate().filter(|&(_, &state)| state == 1).fold(0, |acc, (i, _)| acc | 1 << i)
+    }
+
+    const MOORE: [(i64, i64); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+    fn check_totalistic(rule: &str, birth: &[u32], survival: &[u32], offsets: &[(i64, i64)]) {
+        let rule: Rule = rule.parse().unwrap();
+        for grid in soups(2) {
+            let leaves = quadrants(&grid).map(|cells| bits(&cells));
+            for generations in 0..=4 {
+                let expected = naive(birth, survival, offsets, 2, grid, generations);
+                let rules = vec![rule; generations];
+                assert_eq!(step_bits(&rules, leaves), bits(&expected), "{} after {}", rule, generations);
+                assert_eq!(step_cells(&rules, grid), expected, "{} after {}", rule, generations);
+            }
+        }
+    }
+
+    #[test]
+    fn steps_totalistic_rules_like_counting_neighbors() {
+        check_totalistic("B3/S23", &[3], &[2, 3], &MOORE);
+        check_totalistic("B36/S23", &[3, 6], &[2, 3], &MOORE);
+        check_totalistic("B3678/S34678", &[3, 6, 7, 8], &[3, 4, 6, 7, 8], &MOORE);
+        check_totalistic("B2/S34H", &[2], &[3, 4], &[(-1, -1), (0, -1), (-1, 0), (1, 0), (0, 1), (1, 1)]);
+        check_totalistic("B13/S012V", &[1, 3], &[0, 1, 2], &[(0, -1), (-1, 0), (1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn steps_generations_rules_like_counting_neighbors() {
+        for (rule, birth, survival, states) in [("B2/S/C3", &[2][..], &[][..], 3), ("B2/S345/C4", &[2], &[3, 4, 5], 4)] {
+            let rule: Rule = rule.parse().unwrap();
+            for grid in soups(states) {
+                for generations in 0..=4 {
+                    assert_eq!(step_cells(&vec![rule; generations], grid), naive(birth, survival, &MOORE, states, grid, generations));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn steps_hensel_rules_by_bits_like_by_cells() {
+        for rule in ["B2n3/S23-q", "B3/S2-i34q", "B2-a/S12", "B2ce3aiy/S1c2k3"] {
+            let rule: Rule = rule.parse().unwrap();
+            for grid in soups(2) {
+                let leaves = quadrants(&grid).map(|cells| bits(&cells));
+                for generations in 0..=4 {
+                    let rules = vec![rule; generations];
+                    assert_eq!(step_bits(&rules, leaves), bits(&step_cells(&rules, grid)), "{} after {}", rule, generations);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn splits_and_joins_grids() {
+        let block: [u8; 256] = std::array::from_fn(|i| (i % 251) as u8);
+        assert_eq!(grid(quadrants(&block)), block);
+
+        let cells: [u8; 64] = std::array::from_fn(|i| i as u8 + 1);
+        assert_eq!(middle(&surround(&cells)), cells);
+        assert_eq!(middle_bits(quadrants(&surround(&cells)).map(|cells| bits(&cells))), bits(&cells));
+    }
+}
@@ -1,12 +1,30 @@
-use once_cell::sync::Lazy;
 use wasm_bindgen::prelude::*;
-use std::collections::HashMap;
-use std::sync::{ Mutex, Arc };
-
-use crate::render::{RENDERER, pixel_to_cell};
 
+use crate::algorithm::Algorithm;
+use crate::hashlife::Hashlife;
+use crate::history::{History, Pattern, Snapshot};
+use crate::leaf::LEAF_LEVEL;
+use crate::quicklife::QuickLife;
+use crate::rule::Rule;
+use crate::timeline::Timeline;
+use crate::topology::Topology;
+
+mod algorithm;
+mod arena;
+mod census;
+mod hashlife;
+mod history;
+mod leaf;
+mod quicklife;
 mod render;
 mod parser;
+mod period;
+mod rule;
+#[cfg(test)]
+mod soup;
+mod stats;
+mod timeline;
+mod topology;
 
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
@@ -30,454 +48,977 @@ pub fn init_panic_hook() {
 //     fn log_many(a: &str, b: &str);
 // }
 
-#[derive(Debug, Clone)]
-#[wasm_bindgen]
-pub struct Node {
-    a: NodePtr,
-    b: NodePtr,
-    c: NodePtr,
-    d: NodePtr,
-    population: u32,
-    level: u8,
-    hash: u64,
-}
-
 pub struct Life;
 
-static ON: Node = Node{ a: None, b: None, c: None, d: None, population: 1, level: 0, hash: 1 };
-static OFF: Node = Node{ a: None, b: None, c: None, d: None, population: 0, level: 0, hash: 0 };
-
-type NodePtr = Option<Arc<Node>>;
+// a tick that computes fewer new successors than this was mostly cache hits,
+// so hyperspeed makes the next one bigger
+const HYPERSPEED_MISSES: u64 = 1000;
 
-static NODE: Lazy<Mutex<NodePtr>> = Lazy::new(|| { Mutex::new(Some(Arc::new(OFF.clone()))) });
+const HISTORY_DEPTH: usize = 100;
 
-static JOINCACHE: Lazy<Mutex<HashMap<u64, NodePtr>>> = Lazy::new(|| { Mutex::new(HashMap::new()) });
-static ZEROCACHE: Lazy<Mutex<HashMap<u8, NodePtr>>> = Lazy::new(|| { Mutex::new(HashMap::new()) });
-static SUCCESSORCACHE: Lazy<Mutex<HashMap<(u64, Option<u8>), NodePtr>>> = Lazy::new(|| { Mutex::new(HashMap::new()) });
+const KEYFRAME_INTERVAL: u64 = 256;
+const KEYFRAME_BUDGET: usize = 64 << 20;
+// past this many keyframes they thin out even under budget
+const KEYFRAME_LIMIT: usize = 256;
 
-trait OptionExt {
-    fn hash(&self) -> u64;
-    fn population(&self) -> u32;
-    fn level(&self) -> u8;
-    fn a(&self) -> NodePtr;
-    fn b(&self) -> NodePtr;
-    fn c(&self) -> NodePtr;
-    fn d(&self) -> NodePtr;
+/// A single pattern and everything needed to evolve it. Each universe owns its
+/// own engine, so several can be run side by side on one page.
+///
+/// The pattern is run by hashlife unless QuickLife was chosen with
+/// `set_algorithm`, in which case hashlife sits empty.
+#[wasm_bindgen]
+pub struct Universe {
+    hashlife: Hashlife,
+    quicklife: Option<QuickLife>,
+    generation: u64,
+    rule: Rule,
+    topology: Topology,
+    history: History,
+    timeline: Timeline,
+    // counts edits, so that runs share it and snapshots from before an edit
+    // don't, which tells whether the keyframes still hold after an undo
+    course: u64,
+
+    // each tick steps step_base ^ step_exponent generations
+    step_base: u32,
+    step_exponent: u32,
+    hyperspeed: bool,
 }
 
-impl OptionExt for NodePtr {
-    fn hash(&self) -> u64 { self.as_ref().unwrap().hash }
-    fn population(&self) -> u32 { self.as_ref().unwrap().population }
-    fn level(&self) -> u8 { self.as_ref().unwrap().level }
-    fn a(&self) -> NodePtr { self.as_ref().unwrap().a.clone() }
-    fn b(&self) -> NodePtr { self.as_ref().unwrap().b.clone() }
-    fn c(&self) -> NodePtr { self.as_ref().unwrap().c.clone() }
-    fn d(&self) -> NodePtr { self.as_ref().unwrap().d.clone() }
-}
+impl Universe {
+    pub(crate) fn hashlife(&self) -> &Hashlife {
+        &self.hashlife
+    }
 
-// replace mutex lock with if let
-
-fn join(a: NodePtr, b: NodePtr, c: NodePtr, d: NodePtr) -> NodePtr {
-    let n_hash: u64 = (
-        u64::from(a.level()) + 2 +
-        &a.hash() * 2223243435546756677 +
-        &b.hash() * 1241111124211111421 +
-        &c.hash() * 7532753275327532753 +
-        &d.hash() * 9876503214123056789
-    ) & ((1 << 63) - 1);
-
-    // if a.level() > 3 && JOINCACHE.lock().unwrap().contains_key(&n_hash) {
-    //     return JOINCACHE.lock().unwrap().get(&n_hash).unwrap().clone()
-    // }
-
-    let n_level = &a.level() + 1;
-    let n_population: u32 = a.population() + b.population() + c.population() + d.population();
-
-    let n = Some(Arc::new(Node { 
-        a, b, c, d,
-        population: n_population,
-        level: n_level,
-        hash: n_hash,
-    }));
-
-    // JOINCACHE.lock().unwrap().insert(n_hash, n.clone());
-    n
-}
+    pub(crate) fn quicklife(&self) -> Option<&QuickLife> {
+        self.quicklife.as_ref()
+    }
 
-fn get_zero(k: u8) -> NodePtr {
-    if ZEROCACHE.lock().unwrap().contains_key(&k) {
-        return ZEROCACHE.lock().unwrap().get(&k).unwrap().clone();
+    pub(crate) fn states(&self) -> u8 {
+        self.rule.states()
     }
 
-    let n = if k == 0 {
-        Some(Arc::new(OFF.clone()))
+    pub(crate) fn topology_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        self.topology.bounds()
     }
-    else {
-        join (
-            get_zero(k - 1),
-            get_zero(k - 1),
-            get_zero(k - 1),
-            get_zero(k - 1),
-        )
-    };
-    ZEROCACHE.lock().unwrap().insert(k, n.clone());
-    n
-}
 
-fn life(a: NodePtr, b: NodePtr, c: NodePtr, d: NodePtr, e: NodePtr, 
-        f: NodePtr, g: NodePtr, h: NodePtr, i: NodePtr) -> NodePtr {
-    let outer = [a, b, c, d, f, g, h, i].iter().fold(0, |acc, n| acc + n.population());
+    fn engine(&self) -> &dyn Algorithm {
+        match &self.quicklife {
+            Some(quicklife) => quicklife,
+            None => &self.hashlife,
+        }
+    }
 
-    if (outer == 2 && e.population() == 1) || outer == 3 {
-        Some(Arc::new(ON.clone()))
+    fn engine_mut(&mut self) -> &mut dyn Algorithm {
+        match &mut self.quicklife {
+            Some(quicklife) => quicklife,
+            None => &mut self.hashlife,
+        }
     }
-    else {
-        Some(Arc::new(OFF.clone()))
+
+    fn snapshot(&mut self) -> Snapshot {
+        let pattern = match &self.quicklife {
+            Some(quicklife) => Pattern::QuickLife(Box::new(quicklife.clone())),
+            None => Pattern::Hashlife(self.hashlife.keep()),
+        };
+        Snapshot { pattern, generation: self.generation, rule: self.rule, topology: self.topology, course: self.course }
     }
-}
-fn life_4x4(m: NodePtr) -> NodePtr {
-    let na = life(m.a().a(), m.a().b(), m.b().a(), m.a().c(), m.a().d(), m.b().c(), m.c().a(), m.c().b(), m.d().a());
-    let nb = life(m.a().b(), m.b().a(), m.b().b(), m.a().d(), m.b().c(), m.b().d(), m.c().b(), m.d().a(), m.d().b());
-    let nc = life(m.a().c(), m.a().d(), m.b().c(), m.c().a(), m.c().b(), m.d().a(), m.c().c(), m.c().d(), m.d().c());
-    let nd = life(m.a().d(), m.b().c(), m.b().d(), m.c().b(), m.d().a(), m.d().b(), m.c().d(), m.d().c(), m.d().d());
 
-    join(na, nb, nc, nd)
-}
+    fn restore(&mut self, snapshot: &Snapshot) {
+        match &snapshot.pattern {
+            Pattern::Hashlife(kept) => {
+                self.quicklife = None;
+                self.hashlife.restore(kept);
+                self.hashlife.set_rule(snapshot.rule, snapshot.topology).expect("the rule ran the pattern before");
+            }
+            Pattern::QuickLife(quicklife) => {
+                if self.quicklife.is_none() {
+                    self.hashlife.clear();
+                }
+                self.quicklife = Some((**quicklife).clone());
+            }
+        }
+        self.generation = snapshot.generation;
+        self.rule = snapshot.rule;
+        self.topology = snapshot.topology;
+        self.course = snapshot.course;
+    }
 
-use std::sync::atomic::{AtomicUsize, Ordering};
-static CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
+    fn forget(&mut self, snapshots: Vec<Snapshot>) {
+        for snapshot in snapshots {
+            if let Pattern::Hashlife(kept) = snapshot.pattern {
+                self.hashlife.release(kept);
+            }
+        }
+    }
 
-fn successor(m: NodePtr, j: Option<u8>) -> NodePtr {
-    CALL_COUNT.fetch_add(1, Ordering::SeqCst);
+    // runs a change and records the state before it for undo, unless the
+    // change failed
+    fn record(&mut self, edit: impl FnOnce(&mut Self) -> Result<(), String>) -> Result<(), String> {
+        if self.history.depth() == 0 {
+            return edit(self);
+        }
 
-    if m.population() == 0 {
-        return m.a();
+        let snapshot = self.snapshot();
+        match edit(self) {
+            Ok(()) => {
+                let dropped = self.history.push(snapshot);
+                self.forget(dropped);
+                Ok(())
+            }
+            Err(e) => {
+                self.forget(vec![snapshot]);
+                Err(e)
+            }
+        }
     }
 
-    if m.level() > 3 && SUCCESSORCACHE.lock().unwrap().contains_key(&(m.hash(), j)) {
-        return SUCCESSORCACHE.lock().unwrap().get(&(m.hash(), j)).unwrap().clone();
+    // records a change that starts the pattern on a new course, so the
+    // keyframes of the old one are no use any more
+    fn edit(&mut self, edit: impl FnOnce(&mut Self) -> Result<(), String>) -> Result<(), String> {
+        self.record(edit)?;
+        self.course += 1;
+        self.restart_timeline();
+        Ok(())
     }
-    
-    let s = if m.level() == 2 {
-        life_4x4(m.clone())
+
+    fn restart_timeline(&mut self) {
+        let dropped = self.timeline.clear(None);
+        self.forget(dropped);
+        if self.timeline.interval() != 0 {
+            let snapshot = self.snapshot();
+            self.timeline.insert(snapshot);
+        }
     }
-    else {
-        if let Some(j) = j {
-            Some(std::cmp::min(j, m.level() - 2))
-        } else {
-            Some(m.level() - 2)
-        };
-    
-        let c1 = successor(join(m.a().a(), m.a().b(), m.a().c(), m.a().d()), j);
-        let c2 = successor(join(m.a().b(), m.b().a(), m.a().d(), m.b().c()), j);
-        let c3 = successor(join(m.b().a(), m.b().b(), m.b().c(), m.b().d()), j);
-        let c4 = successor(join(m.a().c(), m.a().d(), m.c().a(), m.c().b()), j);
-        let c5 = successor(join(m.a().d(), m.b().c(), m.c().b(), m.d().a()), j);
-        let c6 = successor(join(m.b().c(), m.b().d(), m.d().a(), m.d().b()), j);
-        let c7 = successor(join(m.c().a(), m.c().b(), m.c().c(), m.c().d()), j);
-        let c8 = successor(join(m.c().b(), m.d().a(), m.c().d(), m.d().c()), j);
-        let c9 = successor(join(m.d().a(), m.d().b(), m.d().c(), m.d().d()), j);
-    
-        if j.unwrap() < m.level() - 2 { 
-            join(
-                join(c1.d(), c2.c(), c4.b(), c5.a()),
-                join(c2.d(), c3.c(), c5.b(), c6.a()),
-                join(c4.d(), c5.c(), c7.b(), c8.a()),
-                join(c5.d(), c6.c(), c8.b(), c9.a()),
-            ) 
-        } else { 
-            join(
-                successor(join(c1.clone(), c2.clone(), c4.clone(), c5.clone()), j),
-                successor(join(c2.clone(), c3.clone(), c5.clone(), c6.clone()), j),
-                successor(join(c4.clone(), c5.clone(), c7.clone(), c8.clone()), j),
-                successor(join(c5.clone(), c6.clone(), c8.clone(), c9.clone()), j),
-            )
-        }
-    };
-    SUCCESSORCACHE.lock().unwrap().insert((m.hash(), j), s.clone());
-    s
-}
 
-fn is_padded(node: NodePtr) -> bool {
-    node.a().population() == node.a().d().d().population() &&
-    node.b().population() == node.b().c().c().population() &&
-    node.c().population() == node.c().b().b().population() &&
-    node.d().population() == node.d().a().a().population()
-}
+    fn keyframe_bytes(&self) -> usize {
+        let kept = self.timeline.snapshots().filter_map(|snapshot| match &snapshot.pattern {
+            Pattern::Hashlife(kept) => Some(kept),
+            Pattern::QuickLife(_) => None,
+        });
+        let tiles: usize = self.timeline.snapshots().map(|snapshot| match &snapshot.pattern {
+            Pattern::Hashlife(_) => 0,
+            Pattern::QuickLife(quicklife) => quicklife.bytes(),
+        }).sum();
+        self.hashlife.kept_bytes(kept) + tiles
+    }
+
+    fn thin_keyframes(&mut self) {
+        let dropped = self.timeline.thin();
+        self.forget(dropped);
+    }
+
+    // steps n generations in one go, so that hashlife can jump as far as it
+    // likes, and takes a keyframe after if one is due
+    fn run(&mut self, n: u64) {
+        self.generation = self.generation.saturating_add(n);
+        self.engine_mut().step(n);
+
+        if self.timeline.is_due(self.generation) {
+            let snapshot = self.snapshot();
+            self.timeline.insert(snapshot);
+            while self.timeline.len() > 1 && (self.timeline.len() > KEYFRAME_LIMIT || self.keyframe_bytes() > self.timeline.budget()) {
+                self.thin_keyframes();
+            }
+        }
+    }
+
+    fn replace_rule(&mut self, rule: &str) -> Result<(), String> {
+        let (rule, topology) = rule.split_once(':').unwrap_or((rule, ""));
+        let (rule, topology): (Rule, Topology) = (rule.parse()?, topology.parse()?);
+        self.engine_mut().set_rule(rule, topology)?;
+        self.rule = rule;
+        self.topology = topology;
+        Ok(())
+    }
 
-fn inner(node: NodePtr) -> NodePtr {
-    join(
-        node.a().d(),
-        node.b().c(),
-        node.c().b(),
-        node.d().a()
-    )
+    fn replace_pattern(&mut self, pts: Vec<i64>, states: Vec<u8>) {
+        if pts.len() % 2 == 1 || states.len() != pts.len() / 2 { return }
+        let states: Vec<u8> = states.into_iter().map(|state| if state < self.rule.states() { state } else { 0 }).collect();
+
+        if pts.is_empty() {
+            self.engine_mut().load(&[], false);
+        }
+        else {
+            // hashlife places the pattern either way, so that it lands on the
+            // same cells whichever engine runs it
+            self.hashlife.construct_states(&pts, &states);
+            if let Some(quicklife) = &mut self.quicklife {
+                quicklife.load(&self.hashlife.cells(), false);
+                self.hashlife.clear();
+            }
+        }
+        self.generation = 0;
+    }
 }
 
-fn center(m: NodePtr) -> NodePtr {
-    let z = get_zero(m.a().level());
-    join(
-        join(z.clone(), z.clone(), z.clone(), m.a()), 
-        join(z.clone(), z.clone(), m.b(), z.clone()), 
-        join(z.clone(), m.c(), z.clone(), z.clone()), 
-        join(m.d(), z.clone(), z.clone(), z),
-    )
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-fn crop(node: NodePtr) -> NodePtr {
-    if node.level() <= 3 || !is_padded(node.clone()) {
-        node
+#[wasm_bindgen]
+impl Universe {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Universe {
+        let mut universe = Universe {
+            hashlife: Hashlife::new(),
+            quicklife: None,
+            generation: 0,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            history: History::new(HISTORY_DEPTH),
+            timeline: Timeline::new(KEYFRAME_INTERVAL, KEYFRAME_BUDGET),
+            course: 0,
+
+            step_base: 2,
+            step_exponent: 0,
+            hyperspeed: false,
+        };
+        universe.restart_timeline();
+        universe
     }
-    else {
-        crop(inner(node))
+
+    /// The engine running the pattern, `HashLife` or `QuickLife`.
+    pub fn algorithm(&self) -> String {
+        match self.quicklife {
+            Some(_) => "QuickLife".to_string(),
+            None => "HashLife".to_string(),
+        }
     }
-}
-fn pad(node: NodePtr) -> NodePtr {
-    if node.level() <= 3 || !is_padded(node.clone()) {
-        pad(center(node))
+
+    /// Switches the engine to `HashLife` or `QuickLife` and carries the
+    /// pattern across. QuickLife can't run on bounded grids.
+    pub fn set_algorithm(&mut self, algorithm: &str) -> Result<(), String> {
+        let quicklife = match algorithm.to_lowercase().as_str() {
+            "hashlife" => false,
+            "quicklife" => true,
+            _ => return Err(format!("Unknown algorithm {}", algorithm)),
+        };
+        if quicklife == self.quicklife.is_some() { return Ok(()) }
+
+        self.edit(|universe| {
+            match universe.quicklife.take() {
+                Some(quicklife) => {
+                    universe.hashlife.set_rule(universe.rule, universe.topology)?;
+                    universe.hashlife.load(&quicklife.cells(), quicklife.background());
+                }
+                None => {
+                    let mut quicklife = QuickLife::new();
+                    quicklife.set_rule(universe.rule, universe.topology)?;
+                    quicklife.load(&universe.hashlife.cells(), universe.hashlife.background());
+                    universe.hashlife.clear();
+                    universe.quicklife = Some(quicklife);
+                }
+            }
+            Ok(())
+        })
     }
-    else {
-        node
+
+    /// Goes back to before the last edit or run. Edits include loading a
+    /// pattern and changing the rule or the engine. Undoing a run keeps the
+    /// keyframes, which are still on its way. Returns false when there is
+    /// nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if !self.history.can_undo() { return false }
+
+        let current = self.snapshot();
+        let snapshot = self.history.undo(current);
+        let course = self.course;
+        self.restore(&snapshot);
+        self.forget(vec![snapshot]);
+        if self.course != course {
+            self.restart_timeline();
+        }
+        true
     }
-}
 
-fn expand_recurse(node: &NodePtr, x: i32, y: i32) -> Vec<i32> {
-    if node.population() == 0 {
-        return Vec::new()
+    /// Takes back the last undo. Returns false when there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if !self.history.can_redo() { return false }
+
+        let current = self.snapshot();
+        let snapshot = self.history.redo(current);
+        let course = self.course;
+        self.restore(&snapshot);
+        self.forget(vec![snapshot]);
+        if self.course != course {
+            self.restart_timeline();
+        }
+        true
     }
 
-    let size = 2_u32.pow(node.level() as u32);
+    pub fn can_undo(&self) -> bool { self.history.can_undo() }
+    pub fn can_redo(&self) -> bool { self.history.can_redo() }
 
-    if node.level() == 0 {
-        vec![x, y]
+    /// Sets how many edits and runs can be undone, 0 turns the history off.
+    /// The oldest are forgotten first.
+    pub fn set_history_depth(&mut self, depth: u32) {
+        let dropped = self.history.set_depth(depth as usize);
+        self.forget(dropped);
     }
-    else {
-        let offset = (size >> 1) as i32;
-        let mut output = Vec::new();
-        output.append(&mut expand_recurse(&node.a(), x, y));
-        output.append(&mut expand_recurse(&node.b(), x + offset, y));
-        output.append(&mut expand_recurse(&node.c(), x, y + offset));
-        output.append(&mut expand_recurse(&node.d(), x + offset, y + offset));
-        output
+
+    pub fn history_depth(&self) -> u32 { self.history.depth() as u32 }
+
+    pub fn hash(&self) -> u64 {
+        match &self.quicklife {
+            Some(quicklife) => {
+                let mut hashlife = Hashlife::new();
+                hashlife.load(&quicklife.cells(), quicklife.background());
+                hashlife.hash()
+            }
+            None => self.hashlife.hash(),
+        }
+    }
+
+    /// The number of visible live cells, a BigInt in JS. It saturates at
+    /// `u64::MAX` for huge patterns and while an infinite background is alive,
+    /// `population_string` has the exact count.
+    pub fn population(&self) -> u64 {
+        self.engine().population()
     }
-}
 
-fn set_cell_recurse(node: NodePtr, x: i32, y: i32, alive: bool) -> NodePtr {
-    if node.level() == 0 {
-        if alive {
-            return Some(Arc::new(ON.clone()))
+    /// The exact population in decimal, or `Infinity` while an infinite
+    /// background is alive.
+    pub fn population_string(&self) -> String {
+        match &self.quicklife {
+            Some(quicklife) if quicklife.background() => "Infinity".to_string(),
+            Some(quicklife) => quicklife.population().to_string(),
+            None => self.hashlife.population_string(),
         }
-        else {
-            return Some(Arc::new(OFF.clone()))
+    }
+
+    /// Whether a `B0` rule has turned the cells around the pattern on.
+    pub fn is_background_alive(&self) -> bool { self.engine().background() }
+
+    /// Caps the memory used by nodes and cached successors, in megabytes. Going
+    /// over it, even part way through a step, clears the caches down to what
+    /// the patterns still use, which costs speed but not correctness. When the
+    /// patterns and the undo history alone don't fit, it is raised to four
+    /// times what they take up.
+    pub fn set_memory_limit(&mut self, megabytes: u32) {
+        self.hashlife.set_memory_limit((megabytes as usize).saturating_mul(1 << 20));
+    }
+
+    pub fn memory_limit(&self) -> u32 { (self.hashlife.memory_limit() >> 20) as u32 }
+
+    /// In debug mode hashlife steps every successor it finds in the cache
+    /// again, once, and counts those that come out different, see
+    /// `collisions`. Successors are cached by node id and no two nodes share
+    /// one, so this should stay 0, unless an id is used after garbage
+    /// collection handed it to a new node.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.hashlife.set_debug(debug);
+    }
+
+    pub fn collisions(&self) -> u32 { self.hashlife.collisions().min(u32::MAX.into()) as u32 }
+
+    /// The level of the smallest quadtree root that holds the pattern.
+    pub fn level(&self) -> u8 {
+        match &self.quicklife {
+            Some(quicklife) => {
+                let extent = quicklife.bounds().map_or(0, |bounds| bounds.map(|c| c.max(-c - 1)).into_iter().max().unwrap());
+                LEAF_LEVEL.max(65 - extent.leading_zeros() as u8)
+            }
+            None => self.hashlife.level(),
         }
     }
-    
-    let offset = (2_u32.pow(node.as_ref().unwrap().level as u32) >> 2) as i32;
-    let (mut a, mut b, mut c, mut d) = (node.a(), node.b(), node.c(), node.d());
 
-    if x >= 0 && y >= 0 {
-        d = set_cell_recurse(node.d(), x - offset, y - offset, alive);
+    pub fn generation(&self) -> u64 { self.generation }
+
+    pub fn rule(&self) -> String { format!("{}{}", self.rule, self.topology) }
+
+    /// Accepts B/S notation (`B36/S23`), S/B notation (`23/3`) or a named rule
+    /// such as `HighLife`, optionally followed by a grid such as `:T30,20`.
+    pub fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        self.edit(|universe| universe.replace_rule(rule))
     }
-    else if x >= 0 && y < 0 {
-        c = set_cell_recurse(node.c(), x - offset, y + offset, alive);
+
+    pub fn topology(&self) -> String { self.topology.to_string() }
+
+    /// Bounds the universe to a grid such as `P30,20`, `T30,20`, `K30*,20` or
+    /// `C30,20`, see `Topology`. An empty string makes it infinite again. Cells
+    /// outside the new grid are removed.
+    pub fn set_topology(&mut self, topology: &str) -> Result<(), String> {
+        let (rule, topology) = (self.rule, topology.trim_start_matches(':').parse()?);
+        self.edit(|universe| {
+            universe.engine_mut().set_rule(rule, topology)?;
+            universe.topology = topology;
+            Ok(())
+        })
     }
-    else if x < 0 && y >= 0 {
-        b = set_cell_recurse(node.b(), x + offset, y - offset, alive);
+
+    /// The live cells as `[x, y]` pairs, shifted so that the smallest
+    /// coordinate is 0.
+    pub fn expand(&self) -> Vec<i64> {
+        let cells = self.engine().cells();
+        let min_x = cells.iter().map(|c| c.0).min().unwrap_or(0);
+        let min_y = cells.iter().map(|c| c.1).min().unwrap_or(0);
+        let min = std::cmp::min(min_x, min_y);
+
+        cells.iter().flat_map(|&(x, y, _)| [x - min, y - min]).collect()
     }
-    else {
-        a = set_cell_recurse(node.a(), x + offset, y + offset, alive);
+
+    pub fn construct(&mut self, pts: Vec<i64>) {
+        let states = vec![1; pts.len() / 2];
+        self.construct_states(pts, states);
     }
-    join(a, b, c, d)
-}
 
-fn is_alive_recurse(node: NodePtr, x: i32, y: i32) -> bool {
-    if node.level() == 0 {
-        node.population() == 1
+    /// Like `construct`, but with a state for each `[x, y]` pair.
+    pub fn construct_states(&mut self, pts: Vec<i64>, states: Vec<u8>) {
+        if pts.is_empty() || pts.len() % 2 == 1 || states.len() != pts.len() / 2 { return }
+
+        let _ = self.edit(|universe| {
+            universe.replace_pattern(pts, states);
+            Ok(())
+        });
     }
-    else {
-        let offset = (2_u32.pow(node.level() as u32) >> 2) as i32;
 
-        if x >= 0 && y >= 0 {
-            is_alive_recurse(node.d(), x - offset, y - offset)
-        }
-        else if x >= 0 && y < 0 {
-            is_alive_recurse(node.c(), x - offset, y + offset)
-        }
-        else if x < 0 && y >= 0 {
-            is_alive_recurse(node.b(), x + offset, y - offset)
+    /// Steps `n` generations, a BigInt in JS. Bounded grids step a generation
+    /// at a time until the pattern repeats, and then skip whole cycles, so a
+    /// long run there costs as much as the pattern takes to settle.
+    pub fn advance(&mut self, n: u64) {
+        if n == 0 { return }
+
+        let _ = self.record(|universe| {
+            universe.run(n);
+            Ok(())
+        });
+    }
+
+    /// Sets the generations a tick steps to `base ^ exponent`, Golly style. The
+    /// exponent is lowered until the step fits in 64 bits.
+    pub fn set_step(&mut self, base: u32, exponent: u32) -> Result<(), String> {
+        if base < 2 {
+            return Err(format!("Invalid step base {}", base));
         }
-        else {
-            is_alive_recurse(node.a(), x + offset, y + offset)
+        self.step_base = base;
+        self.step_exponent = exponent;
+        while u64::from(base).checked_pow(self.step_exponent).is_none() {
+            self.step_exponent -= 1;
         }
+        Ok(())
     }
-}
 
-fn get_bounds_recurse(node: NodePtr, x: i32, y: i32, border: &str) -> Vec<(i32, i32, &str)> {
-    if node.population() == 0 {
-        return vec![(0, 0, border)]
+    pub fn step_base(&self) -> u32 { self.step_base }
+    pub fn step_exponent(&self) -> u32 { self.step_exponent }
+
+    /// The generations the next tick steps, a BigInt in JS.
+    pub fn step_size(&self) -> u64 {
+        u64::from(self.step_base).pow(self.step_exponent)
     }
-    if node.level() == 0 {
-        vec![(x, y, border)]
+
+    /// In hyperspeed the exponent goes up by one after every tick that was
+    /// cheap because its successors were mostly in the cache, which speeds
+    /// up guns and breeders without slowing down chaotic patterns.
+    pub fn set_hyperspeed(&mut self, hyperspeed: bool) {
+        self.hyperspeed = hyperspeed;
     }
-    else {
-        let offset = 2_i32.pow(node.level() as u32) >> 2;
-        let mut output = Vec::new();
 
-        if border == "left" {
-            if node.a().population() > 0  || node.c().population() > 0{
-                output.append(&mut get_bounds_recurse(node.a(), x - offset, y - offset, "left"));
-                output.append(&mut get_bounds_recurse(node.c(), x - offset, y + offset, "left"));
+    pub fn hyperspeed(&self) -> bool { self.hyperspeed }
 
-            }
-            else {
-                output.append(&mut get_bounds_recurse(node.b(), x + offset, y - offset, "left"));
-                output.append(&mut get_bounds_recurse(node.d(), x + offset, y + offset, "left"));
-            }
+    /// Steps `step_size` generations. With base 2 that is a single jump of
+    /// `successor`.
+    pub fn tick(&mut self) {
+        let misses = self.hashlife.successor_misses();
+        self.advance(self.step_size());
+
+        // quicklife is never cheap
+        let cheap = self.quicklife.is_none() && self.hashlife.successor_misses() - misses < HYPERSPEED_MISSES;
+        let fits = u64::from(self.step_base).checked_pow(self.step_exponent + 1).is_some();
+        if self.hyperspeed && cheap && fits && !self.topology.is_bounded() {
+            self.step_exponent += 1;
         }
-        else if node.a().population() > 0 || node.b().population() > 0 {
-            output.append(&mut get_bounds_recurse(node.a(), x - offset, y - offset, "top"));
-            output.append(&mut get_bounds_recurse(node.b(), x + offset, y - offset, "top"));
+    }
+
+    /// Steps forward to an absolute generation.
+    pub fn advance_to(&mut self, generation: u64) -> Result<(), String> {
+        if generation < self.generation {
+            return Err(format!("Can't go back from generation {} to {}", self.generation, generation));
         }
-        else {
-            output.append(&mut get_bounds_recurse(node.c(), x - offset, y + offset, "top"));
-            output.append(&mut get_bounds_recurse(node.d(), x + offset, y + offset, "top"));
+        self.advance(generation - self.generation);
+        Ok(())
+    }
+
+    /// Moves to any generation since the last edit, forwards by stepping and
+    /// backwards by stepping forward from the keyframe before it.
+    pub fn goto_generation(&mut self, generation: u64) -> Result<(), String> {
+        if generation >= self.generation {
+            return self.advance_to(generation);
         }
-        output
+        let start = self.timeline.generations().take_while(|&start| start <= generation).last()
+            .ok_or_else(|| format!("Can't go back to generation {}, from before the last edit", generation))?;
+
+        self.record(|universe| {
+            let keyframe = universe.timeline.take_before(start).expect("a keyframe at the start");
+            universe.restore(&keyframe);
+            universe.timeline.insert(keyframe);
+            universe.run(generation - start);
+            Ok(())
+        })
     }
-}
 
-#[wasm_bindgen]
-impl Life {
-    pub fn hash() -> u64 { NODE.lock().unwrap().hash() }
-    pub fn population() -> u32 { NODE.lock().unwrap().population() }
-    pub fn level() -> u8 { NODE.lock().unwrap().level() }
-
-    pub fn expand(x: i32, y: i32) -> Vec<i32> {
-        let output = expand_recurse(&NODE.lock().unwrap().clone(), x, y);
-        let min_x = output.chunks(2).map(|c| c[0]).min().unwrap();
-        let min_y = output.chunks(2).map(|c| c[1]).min().unwrap();
-        let min = std::cmp::min(min_x, min_y);
+    /// The generations of the keyframes, from the last edit on.
+    pub fn keyframes(&self) -> Vec<u64> {
+        self.timeline.generations().collect()
+    }
+
+    /// Takes a keyframe after a step whenever `interval` generations or more
+    /// have passed since the last one, 0 stops taking them. The interval
+    /// doubles whenever they go over their budget.
+    pub fn set_keyframe_interval(&mut self, interval: u64) {
+        let dropped = self.timeline.clear(Some(interval));
+        self.forget(dropped);
+        self.restart_timeline();
+    }
+
+    pub fn keyframe_interval(&self) -> u64 { self.timeline.interval() }
+
+    /// Caps the memory kept for keyframes, in megabytes. Nodes they share with
+    /// each other or the pattern count once.
+    pub fn set_keyframe_budget(&mut self, megabytes: u32) {
+        self.timeline.set_budget((megabytes as usize).saturating_mul(1 << 20));
+    }
+
+    pub fn keyframe_budget(&self) -> u32 { (self.timeline.budget() >> 20) as u32 }
+
+    pub fn is_alive(&self, x: i64, y: i64) -> bool {
+        self.get_state(x, y) == 1
+    }
 
-        output.iter().map(|c| c - min).collect()
+    pub fn get_state(&self, x: i64, y: i64) -> u8 {
+        self.engine().get_cell(x, y)
     }
 
-    pub fn construct(pts: Vec<i32>) {
-        if pts.is_empty() || pts.len() % 2 == 1 { return }
+    pub fn set_cell(&mut self, x: i64, y: i64, alive: bool) {
+        self.set_state(x, y, u8::from(alive));
+    }
+
+    pub fn set_state(&mut self, x: i64, y: i64, state: u8) {
+        let state = if state < self.rule.states() { state } else { 0 };
+        let _ = self.edit(|universe| {
+            universe.engine_mut().set_cell(x, y, state);
+            Ok(())
+        });
+    }
+
+    /// The smallest and largest x and y of a live cell, as `[min_x, max_x,
+    /// min_y, max_y]`, or all zeros for an empty universe.
+    pub fn get_bounds(&self) -> Vec<i64> {
+        self.engine().bounds().unwrap_or([0; 4]).to_vec()
+    }
+}
+
+// https://github.com/johnhw/hashlife/blob/master/hashlife.py
+// https://rustwasm.github.io/docs/book/game-of-life/implementing.html
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soup::soup;
+
+    // maps a cell of the infinite plane to the cell of a grid it lands on
+    type Fold = fn(i64, i64) -> (i64, i64);
 
-        let x_vals: Vec::<i32> = pts.chunks(2).map(|c| c[0]).collect();
-        let y_vals: Vec::<i32> = pts.chunks(2).map(|c| c[1]).collect();
-        let min_x = x_vals.iter().min().unwrap();
-        let min_y = y_vals.iter().min().unwrap();
+    const GLIDER: [(i64, i64); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
 
-        let mut pattern: HashMap<(i32, i32), NodePtr> = std::collections::HashMap::new();
-        for n in 0..x_vals.len() {
-            pattern.insert(
-                (x_vals[n] - min_x, y_vals[n] - min_y),
-                Some(Arc::new(ON.clone()))
-            );
+    fn glider() -> Universe {
+        let mut universe = Universe::new();
+        universe.construct(GLIDER.iter().flat_map(|&(x, y)| [x, y]).collect());
+        universe
+    }
+
+    fn sorted_cells(universe: &Universe) -> Vec<(i64, i64)> {
+        let mut cells: Vec<(i64, i64)> = universe.engine().cells().into_iter().map(|(x, y, _)| (x, y)).collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn centers_patterns_by_the_smallest_square_holding_them() {
+        // the top left cell lands at minus half the square's side, however
+        // big the leaves are
+        let cases: [(&[i64], [i64; 4]); 6] = [
+            (&[5, 5], [-1, -1, -1, -1]),
+            (&[0, 0, 1, 1], [-1, 0, -1, 0]),
+            (&[0, 0, 3, 0], [-2, 1, -2, -2]),
+            (&[0, 0, 4, 0], [-4, 0, -4, -4]),
+            (&[0, 0, 7, 7], [-4, 3, -4, 3]),
+            (&[0, 0, 8, 0], [-8, 0, -8, -8]),
+        ];
+        for (pts, bounds) in cases {
+            let mut universe = Universe::new();
+            universe.construct(pts.to_vec());
+            assert_eq!(universe.get_bounds(), bounds, "{:?}", pts);
         }
+        assert_eq!(glider().get_bounds(), [-2, 0, -2, 0]);
+    }
 
-        let mut k = 0;
-        let mut last_updated = (0, 0);
-        while pattern.len() != 1 {
-            let mut next_level = std::collections::HashMap::new();
-            let z = get_zero(k);
+    #[test]
+    fn constructs_patterns_spanning_all_of_i64() {
+        let mut universe = Universe::new();
+        let pts = vec![i64::MIN, 0, i64::MAX, 0, 0, i64::MIN, 0, i64::MAX];
+        universe.construct(pts.clone());
+        assert_eq!(universe.population(), 4);
+        assert_eq!(universe.get_bounds(), [i64::MIN, i64::MAX, i64::MIN, i64::MAX]);
+        // a pattern that wide fills the square it is centered in
+        assert!(pts.chunks(2).all(|c| universe.is_alive(c[0], c[1])));
+
+        // and its cells are far outside a small grid
+        universe.set_topology("T10,10").unwrap();
+        universe.construct(pts);
+        assert_eq!(universe.population(), 0);
+    }
 
-            while !pattern.is_empty() {
-                let (mut x, mut y) = pattern.iter().next().unwrap().0;
-                x = x - (x & 1);
-                y = y - (y & 1);
+    #[test]
+    fn wraps_patterns_around_tori_and_klein_bottles() {
+        // a glider on an 8x8 grid runs like one on the infinite plane, folded
+        // onto the grid, since it never meets its own images
+        let folds: [(&str, Fold); 3] = [
+            ("T8,8", |x, y| (x.rem_euclid(8), y.rem_euclid(8))),
+            ("K8*,8", |x, y| {
+                let x = if y.div_euclid(8).rem_euclid(2) == 0 { x } else { 7 - x };
+                (x.rem_euclid(8), y.rem_euclid(8))
+            }),
+            ("K8,8*", |x, y| {
+                let y = if x.div_euclid(8).rem_euclid(2) == 0 { y } else { 7 - y };
+                (x.rem_euclid(8), y.rem_euclid(8))
+            }),
+        ];
+        for (topology, fold) in folds {
+            let mut infinite = Universe::new();
+            let mut bounded = Universe::new();
+            bounded.set_topology(topology).unwrap();
+            for (x, y) in GLIDER {
+                infinite.set_cell(x, y, true);
+                bounded.set_cell(x - 4, y - 4, true);
+            }
 
-                let a = pattern.remove(&(x, y)).unwrap_or(z.clone());
-                let b = pattern.remove(&(x + 1, y)).unwrap_or(z.clone());
-                let c = pattern.remove(&(x, y + 1)).unwrap_or(z.clone());
-                let d = pattern.remove(&(x + 1, y + 1)).unwrap_or(z.clone());
+            for generation in 1..=40 {
+                infinite.advance(1);
+                bounded.advance(1);
+                let mut folded: Vec<(i64, i64)> = sorted_cells(&infinite).into_iter().map(|(x, y)| fold(x, y)).collect();
+                folded.sort();
+                let wrapped: Vec<(i64, i64)> = sorted_cells(&bounded).into_iter().map(|(x, y)| (x + 4, y + 4)).collect();
+                assert_eq!(wrapped, folded, "{} at {}", topology, generation);
+            }
+        }
+    }
 
-                last_updated = (x >> 1, y >> 1);
-                next_level.insert((x >> 1, y >> 1), join(a, b, c, d));
+    #[test]
+    fn clips_patterns_that_ran_far_to_a_new_grid() {
+        let mut universe = glider();
+        universe.advance(1 << 40);
+        universe.set_topology("T10,10").unwrap();
+        assert_eq!(universe.population(), 0);
+
+        let mut universe = glider();
+        universe.set_topology("P10,10").unwrap();
+        universe.advance(100);
+        // the glider turns into a block in the corner of a dead edged plane
+        assert_eq!(universe.population(), 4);
+    }
 
+    #[test]
+    fn wraps_the_edges_of_huge_grids() {
+        // far from the edges a pattern runs as on the infinite plane, however
+        // long the edges are, B0 backgrounds beyond dead edges included
+        for (rule, topology) in [("B3/S23", "T100000,100000"), ("B3/S23", "K2147483647*,2147483647"), ("B013/S3", "P2147483647")] {
+            let (mut infinite, mut bounded) = (Universe::new(), Universe::new());
+            bounded.set_rule(&format!("{}:{}", rule, topology)).unwrap();
+            infinite.set_rule(rule).unwrap();
+            for universe in [&mut infinite, &mut bounded] {
+                for (x, y) in soup(8, 0) {
+                    universe.set_cell(x, y, true);
+                }
+                universe.advance(10);
+            }
+            for (x, y) in (-20..28).flat_map(|y| (-20..28).map(move |x| (x, y))) {
+                assert_eq!(bounded.get_state(x, y), infinite.get_state(x, y), "{} at {}, {}", topology, x, y);
             }
-            pattern = next_level;
-            k += 1;
         }
-        let mut node = NODE.lock().unwrap();
-        *node = pad(pattern[&last_updated].clone());
+        assert!(Universe::new().set_topology("T2147483648").is_err());
     }
 
-    pub fn advance(mut n: u32) {
-        if n == 0 { return }
+    // steps the cells in a window, and every cell outside it as the background
+    struct Reference {
+        rule: Rule,
+        size: i64,
+        cells: Vec<u8>,
+        background: u8,
+    }
+
+    impl Reference {
+        fn get(&self, x: i64, y: i64) -> u8 {
+            if (0..self.size).contains(&x) && (0..self.size).contains(&y) { self.cells[(y * self.size + x) as usize] } else { self.background }
+        }
 
-        let mut node = NODE.lock().unwrap();
-        let mut bits = Vec::new();
-        while n > 0 {
-            bits.push(n & 1);
-            n >>= 1;
-            *node = center(node.clone());
+        fn step(&mut self) {
+            self.cells = (0..self.size * self.size).map(|i| {
+                let (x, y) = (i % self.size, i / self.size);
+                let neighborhood = (0..9)
+                    .filter(|i| self.get(x + i % 3 - 1, y + i / 3 - 1) == 1)
+                    .fold(0, |acc, i| acc | 1 << i);
+                self.rule.next_state(self.get(x, y), neighborhood)
+            }).collect();
+            self.background = self.rule.next_state(self.background, if self.background == 1 { 0x1FF } else { 0 });
         }
+    }
 
-        for (k, bit) in bits.iter().rev().enumerate() {
-            let j: u8 = (bits.iter().len() - k - 1).try_into().unwrap();
-            if bit != &0 {
-                *node = successor(pad(node.clone()), Some(j));
+    #[test]
+    fn runs_b0_rules_like_a_reference_stepper() {
+        let generations = 24;
+        let (size, margin) = (12, generations + 2);
+
+        for rule in ["B013/S3", "B0123478/S34678", "B02ck/S2-c", "B03/S012345678", "B0/S8"] {
+            for algorithm in ["HashLife", "QuickLife"] {
+                let mut universe = Universe::new();
+                universe.set_rule(rule).unwrap();
+                universe.set_algorithm(algorithm).unwrap();
+                let mut reference = Reference {
+                    rule: rule.parse().unwrap(),
+                    size: size + 2 * margin,
+                    cells: vec![0; ((size + 2 * margin) * (size + 2 * margin)) as usize],
+                    background: 0,
+                };
+                for (x, y) in soup(size, margin) {
+                    universe.set_cell(x, y, true);
+                    reference.cells[(y * reference.size + x) as usize] = 1;
+                }
+
+                for generation in 1..=generations {
+                    universe.advance(1);
+                    reference.step();
+                    assert_eq!(universe.is_background_alive(), reference.background == 1, "{} {} at {}", rule, algorithm, generation);
+                    for y in -1..=reference.size {
+                        for x in -1..=reference.size {
+                            assert_eq!(universe.get_state(x, y), reference.get(x, y), "{} {} at {}, ({}, {})", rule, algorithm, generation, x, y);
+                        }
+                    }
+                }
             }
         }
+    }
 
-        // log(format!("{:?}", CALL_COUNT.load(Ordering::SeqCst)).as_str());
-        CALL_COUNT.store(0, Ordering::SeqCst);
+    #[test]
+    fn runs_b0_rules_on_planes_with_dead_edges() {
+        let size = 12;
+        for rule in ["B013/S3", "B0123478/S34678"] {
+            let mut universe = Universe::new();
+            universe.set_rule(&format!("{}:P{}", rule, size)).unwrap();
+            let mut reference = Reference { rule: rule.parse().unwrap(), size, cells: vec![0; (size * size) as usize], background: 0 };
+            let (left, top) = (-size / 2, -size / 2);
+            for (x, y) in soup(size, 0) {
+                universe.set_cell(left + x, top + y, true);
+                reference.cells[(y * size + x) as usize] = 1;
+            }
+
+            for generation in 1..=24 {
+                universe.advance(1);
+                reference.step();
+                reference.background = 0;
+                for (x, y) in (0..size).flat_map(|y| (0..size).map(move |x| (x, y))) {
+                    assert_eq!(universe.get_state(left + x, top + y), reference.get(x, y), "{} at {}", rule, generation);
+                }
+            }
+        }
+    }
 
-        *node = crop(node.clone());
+    #[test]
+    fn jumps_b0_rules_like_single_steps() {
+        for rule in ["B013/S3", "B0123478/S34678", "B02ck/S2-c"] {
+            let load = || {
+                let mut universe = Universe::new();
+                universe.set_rule(rule).unwrap();
+                for (x, y) in soup(16, 0) {
+                    universe.set_cell(x, y, true);
+                }
+                universe
+            };
+            let (mut stepped, mut jumped) = (load(), load());
+
+            for _ in 0..77 {
+                stepped.advance(1);
+            }
+            jumped.advance(77);
+            assert_eq!(jumped.is_background_alive(), stepped.is_background_alive(), "{}", rule);
+            assert_eq!(jumped.population(), stepped.population(), "{}", rule);
+            assert_eq!(jumped.hash(), stepped.hash(), "{}", rule);
+        }
     }
 
-    pub fn is_alive(x: i32, y: i32) -> bool {
-        let node = NODE.lock().unwrap();
-        // best not to ask why x and y are swapped
-        is_alive_recurse(node.clone(), y, x)
+    #[test]
+    fn advances_to_absolute_generations() {
+        let mut universe = glider();
+        let start = universe.get_bounds();
+        // a glider moves a cell diagonally every 4 generations
+        let moved = |cells: i64| start.iter().map(|c| c + cells).collect::<Vec<i64>>();
+        universe.advance_to(12).unwrap();
+        assert_eq!(universe.get_bounds(), moved(3));
+        universe.advance_to(1 << 40).unwrap();
+        assert_eq!(universe.generation(), 1 << 40);
+        assert_eq!(universe.get_bounds(), moved(1 << 38));
+        assert!(universe.advance_to(12).is_err());
+
+        // the generation is kept in RLE
+        let mut loaded = Universe::new();
+        loaded.load_rle(universe.save_rle(String::new())).unwrap();
+        assert_eq!(loaded.generation(), 1 << 40);
+        loaded.advance_to((1 << 40) + 4).unwrap();
+        assert_eq!(loaded.population(), 5);
     }
 
-    pub fn set_cell(x: i32, y: i32, alive: bool) {
-        let mut node = NODE.lock().unwrap();
-        let renderer = RENDERER.lock().unwrap();
-        let cells = pixel_to_cell(&renderer, x, y);
-        *node = set_cell_recurse(node.clone(), cells.1, cells.0, alive);
+    #[test]
+    fn speeds_up_cheap_ticks_in_hyperspeed() {
+        let mut universe = glider();
+        let start = universe.get_bounds()[0];
+        universe.set_step(2, 3).unwrap();
+        universe.tick();
+        assert_eq!((universe.step_size(), universe.generation()), (8, 8));
+
+        // a glider takes few new successors at any size, so every tick is
+        // cheap and the next one is twice as big
+        universe.set_hyperspeed(true);
+        for _ in 0..20 {
+            universe.tick();
+        }
+        assert_eq!((universe.step_exponent(), universe.generation()), (23, 1 << 23));
+        assert_eq!(universe.get_bounds()[0], start + (1 << 21));
+
+        assert!(universe.set_step(1, 3).is_err());
+        universe.set_step(10, 30).unwrap();
+        assert_eq!((universe.step_exponent(), universe.step_size()), (19, 10_u64.pow(19)));
     }
 
-    // this can and should be done better in the future
-    pub fn get_bounds() -> Vec<i32> {
-        let node = NODE.lock().unwrap();
+    #[test]
+    fn runs_past_level_64() {
+        // a jump this big grows the root well past level 64
+        let mut universe = glider();
+        let start = universe.get_bounds()[0];
+        universe.advance(1 << 62);
+        assert_eq!((universe.population(), universe.get_bounds()[0]), (5, start + (1 << 60)));
+
+        let mut universe = Universe::new();
+        let far = i64::MAX - 10;
+        for (x, y) in GLIDER {
+            universe.set_cell(far + x, far + y, true);
+        }
+        universe.advance(4);
+        assert_eq!(universe.get_bounds(), [far + 1, far + 3, far + 1, far + 3]);
+        assert!(universe.is_alive(far + 2, far + 1));
+
+        let mut universe = glider();
+        let mut generation = 0_u64;
+        universe.set_hyperspeed(true);
+        for _ in 0..70 {
+            generation = generation.saturating_add(universe.step_size());
+            universe.tick();
+        }
+        assert!(universe.step_exponent() > 32);
+        assert_eq!((universe.generation(), universe.population()), (generation, 5));
+    }
 
-        let left = get_bounds_recurse(node.clone(), 0, 0, "left");
-        let top = get_bounds_recurse(node.clone(), 0, 0, "top");
+    #[test]
+    fn carries_the_pattern_across_engines() {
+        let load = || {
+            let mut universe = Universe::new();
+            for (x, y) in soup(16, 0) {
+                universe.set_cell(x, y, true);
+            }
+            universe
+        };
+        let (mut switched, mut hashlife) = (load(), load());
+        switched.advance(100);
+        switched.set_algorithm("quicklife").unwrap();
+        assert_eq!(switched.algorithm(), "QuickLife");
+        switched.advance(100);
+        assert!(switched.set_topology("T20,20").is_err());
+        switched.set_algorithm("HashLife").unwrap();
+        switched.advance(100);
+
+        hashlife.advance(300);
+        assert_eq!((switched.algorithm(), switched.generation()), ("HashLife".to_string(), 300));
+        assert_eq!(switched.hash(), hashlife.hash());
+        assert_eq!(switched.get_bounds(), hashlife.get_bounds());
+        assert!(switched.set_algorithm("Golly").is_err());
+    }
 
-        let min_x = left.iter().map(|x| x.0).min().unwrap() + 1;
-        let min_y = top.iter().map(|y| y.1).min().unwrap() + 1;
+    #[test]
+    fn undoes_and_redoes_edits_and_runs() {
+        let mut universe = glider();
+        let start = universe.hash();
+        universe.set_cell(100, 100, true);
+        universe.advance(10);
+        let (ran, bounds) = (universe.hash(), universe.get_bounds());
+        universe.set_rule("HighLife").unwrap();
+        assert!(universe.set_rule("B9").is_err());
+
+        assert!(universe.undo());
+        assert_eq!((universe.rule().as_str(), universe.generation()), ("B3/S23", 10));
+        assert!(universe.undo());
+        assert_eq!((universe.generation(), universe.population()), (0, 6));
+        assert!(universe.undo());
+        assert_eq!(universe.hash(), start);
+
+        assert!(universe.redo() && universe.redo());
+        assert_eq!((universe.hash(), universe.get_bounds()), (ran, bounds));
+        assert!(universe.can_redo());
+        universe.set_cell(0, 0, false);
+        assert!(!universe.can_redo() && !universe.redo());
+
+        universe.set_history_depth(0);
+        assert!(!universe.can_undo() && !universe.undo());
+    }
 
-        let output = expand_recurse(&node, 0, 0);
-        let expand_min_x = output.chunks(2).map(|c| c[0]).min().unwrap();
-        let expand_min_y = output.chunks(2).map(|c| c[1]).min().unwrap();
-        let min = std::cmp::min(expand_min_x, expand_min_y);
-        let expanded: Vec<i32> = output.iter().map(|c| c - min).collect();
+    #[test]
+    fn goes_back_to_earlier_generations_from_keyframes() {
+        let mut universe = Universe::new();
+        for (x, y) in soup(16, 0) {
+            universe.set_cell(x, y, true);
+        }
+        universe.set_keyframe_interval(16);
+        let mut hashes = vec![universe.hash()];
+        for _ in 0..100 {
+            universe.advance(1);
+            hashes.push(universe.hash());
+        }
+        assert_eq!(universe.keyframes(), (0..=96).step_by(16).collect::<Vec<u64>>());
 
-        let x = expanded.chunks(2).map(|c| c[0]).max().unwrap();
-        let y = expanded.chunks(2).map(|c| c[1]).max().unwrap();
+        for generation in [37, 90, 5, 64, 100, 0, 120] {
+            universe.goto_generation(generation).unwrap();
+            assert_eq!(universe.generation(), generation);
+            if let Some(&hash) = hashes.get(generation as usize) {
+                assert_eq!(universe.hash(), hash, "{}", generation);
+            }
+        }
 
-        vec![min_x, min_x + x, min_y, min_y + y]
+        // undo and redo stay on the same run, so the keyframes hold
+        let keyframes = universe.keyframes();
+        universe.goto_generation(50).unwrap();
+        assert!(universe.undo());
+        assert_eq!((universe.generation(), universe.keyframes()), (120, keyframes.clone()));
+        assert!(universe.redo());
+        assert_eq!((universe.generation(), universe.keyframes()), (50, keyframes));
+
+        universe.set_cell(-5, -5, true);
+        assert_eq!(universe.keyframes(), [50]);
+        assert!(universe.goto_generation(10).is_err());
+        assert!(universe.undo());
+        assert_eq!((universe.generation(), universe.keyframes()), (50, vec![50]));
     }
 
-    // needs revision, don't use for now.
-    pub fn ffwd(n: u32) {
-        let mut node = NODE.lock().unwrap();
-        for _ in 0..n {
-            while node.level() < 3 || 
-                node.a().population() != node.a().d().d().population() ||
-                node.b().population() != node.b().c().c().population() ||
-                node.c().population() != node.c().b().b().population() ||
-                node.d().population() != node.d().a().a().population() {
-                *node = center(node.clone());
-            }
-            *node = successor(node.clone(), None);
+    #[test]
+    fn thins_keyframes_that_go_over_their_budget() {
+        let mut universe = Universe::new();
+        for (x, y) in soup(16, 0) {
+            universe.set_cell(x, y, true);
+        }
+        universe.set_keyframe_interval(16);
+        universe.timeline.set_budget(1);
+        for _ in 0..100 {
+            universe.advance(1);
         }
+        assert_eq!(universe.keyframes(), [0]);
     }
 }
-
-// https://github.com/johnhw/hashlife/blob/master/hashlife.py
-// https://rustwasm.github.io/docs/book/game-of-life/implementing.html
\ No newline at end of file
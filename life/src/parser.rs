@@ -1,5 +1,9 @@
 use wasm_bindgen::prelude::*;
-use crate::Life;
+use crate::{Life, Universe};
+use crate::census::{evolve, symmetries};
+use crate::period::{find_period, Periodicity};
+use crate::rule::Rule;
+use crate::topology::Topology;
 use regex::Regex;
 
 #[wasm_bindgen]
@@ -14,33 +18,45 @@ extern "C" {
     fn log_many(a: &str, b: &str);
 }
 
-fn to_rle(mut pts: Vec<(i32, i32)>) -> (String, (i32, i32)) {
+// the RLE of the cells and the width and height of their bounds, with states
+// written as A-X, prefixed by p-y past 24, for multi-state rules
+pub(crate) fn to_rle(mut pts: Vec<(i64, i64, u8)>, multistate: bool) -> (String, (i64, i64)) {
+    if pts.is_empty() {
+        return ("!".to_string(), (0, 0));
+    }
     let max_x = pts.iter().map(|p| p.0).max().unwrap();
     let max_y = pts.iter().map(|p| p.1).max().unwrap();
     let min_x = pts.iter().map(|p| p.0).min().unwrap();
     let min_y = pts.iter().map(|p| p.1).min().unwrap();
 
-    pts.sort_by(|a, b| (a.1).cmp(&b.1));
+    pts.sort_by_key(|p| (p.1, p.0));
+
+    let dead = if multistate { "." } else { "b" };
+    let live = |state: u8| match state {
+        _ if !multistate => "o".to_string(),
+        1..=24 => char::from(b'A' + state - 1).to_string(),
+        _ => format!("{}{}", char::from(b'p' + (state - 1) / 24 - 1), char::from(b'A' + (state - 1) % 24)),
+    };
 
     let mut line = 0;
     let mut x = 0;
     let mut stars = 0;
+    let mut state = 0;
     let mut out: Vec<String> = Vec::new();
 
-    fn flush_stars(stars: i32, mut out: Vec<String>) -> Vec<String> {
+    let flush_stars = |stars: i64, state: u8, out: &mut Vec<String>| {
         match stars {
-            1 => out.push("o".to_string()),
-            ref s if *s > 1 => out.push(format!("{}o", s)),
+            1 => out.push(live(state)),
+            s if s > 1 => out.push(format!("{}{}", s, live(state))),
             _ => {}
         }
-        out
-    }
+    };
 
     for pt in pts.iter() {
-        let pt = (pt.0 - min_x, pt.1 - min_y);
+        let (pt, cell) = ((pt.0 - min_x, pt.1 - min_y), pt.2);
 
         if pt.1 != line {
-            out = flush_stars(stars, out);
+            flush_stars(stars, state, &mut out);
 
             let reps = pt.1 - line;
             if reps != 1 {
@@ -55,40 +71,172 @@ fn to_rle(mut pts: Vec<(i32, i32)>) -> (String, (i32, i32)) {
             x = 0;
         }
 
-        let mut cts = 0;
-        while x != pt.0 {
-            x += 1;
-            cts += 1;
-        }
+        // cells can be far apart, so skip the gap in one go
+        let cts = pt.0 - x;
+        x = pt.0;
 
-        if cts != 0 {
-            out = flush_stars(stars, out);
+        if cts != 0 || cell != state {
+            flush_stars(stars, state, &mut out);
 
             if cts == 1 {
-                out.push("b".to_string());
+                out.push(dead.to_string());
             }
-            else {
-                out.push(format!("{}b", cts));
+            else if cts > 1 {
+                out.push(format!("{}{}", cts, dead));
             }
             stars = 0;
+            state = cell;
         }
 
         stars += 1;
         x += 1;
     }
 
-    out = flush_stars(stars, out);
+    flush_stars(stars, state, &mut out);
     out.push("!".to_string());
-    (out.join(""), (max_x - min_x, max_y - min_y))
+    (out.join(""), (max_x - min_x + 1, max_y - min_y + 1))
+}
+
+// reads the rule out of a header line like "x = 3, y = 3, rule = B3/S23"
+fn parse_rle_header(line: &str) -> Option<String> {
+    line.split(',')
+        .filter_map(|field| field.split_once('='))
+        .find(|(key, _)| key.trim() == "rule")
+        .map(|(_, value)| value.trim().to_string())
+}
+
+// whether the cells of an RLE with this header rule are written as states,
+// which rules that can't be parsed, such as LifeHistory, most likely are
+fn is_multistate(rule: &str) -> bool {
+    let (rule, _) = rule.split_once(':').unwrap_or((rule, ""));
+    rule.parse::<Rule>().map_or(true, |rule| rule.states() > 2)
+}
+
+// reads the generation out of a Golly style "#CXRLE Pos=0,0 Gen=1234" line
+fn parse_rle_generation(rle: &str) -> Option<u64> {
+    rle.lines()
+        .filter(|line| line.trim_start().starts_with("#CXRLE"))
+        .flat_map(|line| line.split_whitespace())
+        .find_map(|field| field.strip_prefix("Gen="))
+        .and_then(|generation| generation.parse().ok())
+}
+
+// writes cells in the extended Wechsler format that follows the prefix of an
+// apgcode: strips of 5 rows, each column a digit from 0 to v with the top row
+// as its lowest bit, runs of blank columns shortened to w, x or y and a z
+// between strips
+fn to_wechsler(pts: &[(i64, i64)]) -> String {
+    let min_x = pts.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_y = pts.iter().map(|p| p.1).min().unwrap_or(0);
+    let width = pts.iter().map(|p| p.0 - min_x + 1).max().unwrap_or(0);
+    let height = pts.iter().map(|p| p.1 - min_y + 1).max().unwrap_or(0);
+
+    let mut strips = vec![vec![0_u32; width as usize]; (height as usize).div_ceil(5)];
+    for &(x, y) in pts {
+        let (x, y) = ((x - min_x) as usize, (y - min_y) as usize);
+        strips[y / 5][x] |= 1 << (y % 5);
+    }
+
+    let strips: Vec<String> = strips.iter().map(|strip| {
+        let mut out = String::new();
+        let mut blanks = 0;
+        for &column in strip {
+            if column == 0 {
+                blanks += 1;
+                continue;
+            }
+            // blank columns at the end of a strip are left out
+            while blanks > 0 {
+                let run = blanks.min(39);
+                match run {
+                    1 => out.push('0'),
+                    2 => out.push('w'),
+                    3 => out.push('x'),
+                    _ => {
+                        out.push('y');
+                        out.push(char::from_digit(run - 4, 36).unwrap());
+                    }
+                }
+                blanks -= run;
+            }
+            out.push(char::from_digit(column, 32).unwrap());
+        }
+        out
+    }).collect();
+    strips.join("z")
+}
+
+fn from_wechsler(code: &str) -> Result<Vec<(i64, i64)>, String> {
+    let mut pts = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    let mut chars = code.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            'w' => x += 2,
+            'x' => x += 3,
+            'y' => {
+                let run = chars.next().and_then(|char| char.to_digit(36))
+                    .ok_or_else(|| format!("Invalid apgcode {}", code))?;
+                x += 4 + i64::from(run);
+            }
+            'z' => {
+                x = 0;
+                y += 5;
+            }
+            _ => {
+                let column = char.to_digit(32).ok_or_else(|| format!("Invalid apgcode character {}", char))?;
+                pts.extend((0..5).filter(|row| column >> row & 1 == 1).map(|row| (x, y + row)));
+                x += 1;
+            }
+        }
+    }
+    Ok(pts)
+}
+
+/// The apgcode of two-state cells that repeat within `max_period`
+/// generations, like `xs4_33` for a block, `xp2_7` for a blinker or `xq4_153`
+/// for a glider. Of every phase under every symmetry of the neighborhood, the
+/// shortest code is taken, and the first in ASCII order among those.
+pub(crate) fn to_apgcode(rule: &Rule, cells: &[(i64, i64, u8)], max_period: u64) -> Result<String, String> {
+    if cells.is_empty() {
+        return Err("An empty pattern has no apgcode".to_string());
+    }
+    if cells.iter().any(|&(_, _, state)| state > 1) {
+        return Err("Apgcodes only hold cells that are alive or dead".to_string());
+    }
+
+    let period = find_period(*rule, Topology::default(), cells, false, max_period)
+        .filter(|period| period.start == 0 && period.kind != Periodicity::Empty)
+        .ok_or_else(|| format!("The pattern doesn't repeat within {} generations", max_period))?;
+    let prefix = match period.kind {
+        Periodicity::StillLife | Periodicity::Empty => format!("xs{}", cells.len()),
+        Periodicity::Oscillator => format!("xp{}", period.period),
+        Periodicity::Spaceship => format!("xq{}", period.period),
+    };
+
+    let symmetries = symmetries(rule);
+    let code = evolve(rule, cells, period.period - 1).iter()
+        .flat_map(|phase| symmetries.iter().map(move |map| {
+            let pts: Vec<(i64, i64)> = phase.iter().map(|&(x, y, _)| map(x, y)).collect();
+            to_wechsler(&pts)
+        }))
+        .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+        .unwrap();
+    Ok(format!("{}_{}", prefix, code))
 }
 
-#[wasm_bindgen]
 impl Life {
-    pub fn parse_rle(rle: String) -> Vec<i32> {
+    // multi-state patterns write states 1 to 255 as A-X, optionally prefixed by
+    // p-y, two-state ones don't care about case
+    pub(crate) fn parse_rle_pattern(rle: &str) -> (Vec<i64>, Vec<u8>, Option<String>) {
         let lines = rle.split('\n');
-        let mut positions: Vec<i32> = Vec::new();
-        let mut x: i32 = 0;
-        let mut y: i32 = 0;
+        let mut positions: Vec<i64> = Vec::new();
+        let mut states: Vec<u8> = Vec::new();
+        let mut rule = None;
+        let mut multistate = false;
+        let mut x: i64 = 0;
+        let mut y: i64 = 0;
         let mut complete = false;
 
         for line in lines {
@@ -106,40 +254,53 @@ impl Life {
                     for p in coords.chars() {
                         if p.is_ascii_digit() {
                             if x == 0 {
-                                x = p.to_digit(10).unwrap() as i32;
+                                x = i64::from(p.to_digit(10).unwrap());
                             }
                             else {
-                                y = p.to_digit(10).unwrap() as i32;
+                                y = i64::from(p.to_digit(10).unwrap());
                             }
                         }
                     }
                 }
             }
             else if line.starts_with('x') {
-                // size is not needed
-                continue;
+                // the size is not needed, only the rule
+                rule = parse_rle_header(line);
+                multistate = rule.as_deref().is_some_and(is_multistate);
             }
             else {
-                let mut count: i32 = 0;
+                let mut count: i64 = 0;
+                let mut prefix: u32 = 0;
 
                 for char in line.chars() {
                     if char.is_ascii_digit() {
                         count *= 10;
-                        count += char.to_string().parse::<i32>().unwrap();
+                        count += char.to_string().parse::<i64>().unwrap();
                         continue;
                     }
 
                     count = if count == 0 { 1 } else { count };
-                    match char.to_lowercase().next().unwrap() {
-                        'b' => {
+                    let char = if multistate { char } else { char.to_ascii_lowercase() };
+                    match char {
+                        'b' | '.' => {
                             x += count;
                         }
-                        'o' => {
+                        'o' | 'A'..='X' => {
+                            let state = match char {
+                                'o' => 1,
+                                _ => prefix + (char as u32 - 'A' as u32) + 1,
+                            };
                             for _ in 0..count {
                                 positions.push(x);
                                 positions.push(y);
+                                states.push(state.min(255) as u8);
                                 x += 1;
                             }
+                            prefix = 0;
+                        }
+                        'p'..='y' => {
+                            prefix = (char as u32 - 'p' as u32 + 1) * 24;
+                            continue;
                         }
                         '$' => {
                             y += count;
@@ -158,24 +319,49 @@ impl Life {
             }
         }
 
-        positions
+        (positions, states, rule)
+    }
+}
+
+#[wasm_bindgen]
+impl Life {
+    pub fn parse_rle(rle: String) -> Vec<i64> {
+        Self::parse_rle_pattern(&rle).0
+    }
+
+    /// The state of each cell returned by `parse_rle`.
+    pub fn parse_rle_states(rle: String) -> Vec<u8> {
+        Self::parse_rle_pattern(&rle).1
     }
 
-    pub fn convert_rle(pts: Vec<i32>, comment_string: String) -> String {
-        let (rle, (x, y)) = to_rle(pts.chunks(2).map(|c| (c[0], c[1])).collect());
+    /// The `rule =` field of the RLE header, if there is one.
+    pub fn parse_rle_rule(rle: String) -> Option<String> {
+        Self::parse_rle_pattern(&rle).2
+    }
+
+    pub fn convert_rle(pts: Vec<i64>, comment_string: String, rule: String) -> String {
+        let states = vec![1; pts.len() / 2];
+        Self::convert_rle_states(pts, states, comment_string, rule)
+    }
+
+    /// Like `convert_rle`, but with a state for each `[x, y]` pair, written as
+    /// letters when the rule has more than two states.
+    pub fn convert_rle_states(pts: Vec<i64>, states: Vec<u8>, comment_string: String, rule: String) -> String {
+        let cells = pts.chunks(2).zip(states).map(|(c, state)| (c[0], c[1], state)).collect();
+        let (rle, (x, y)) = to_rle(cells, is_multistate(&rule));
         let mut output = Vec::new();
 
         let comments = comment_string.split('\n');
         for comment in comments {
             output.push(format!("#C {}", comment));
         }
-        output.push(format!("x = {}, y = {}\n", x, y));
+        output.push(format!("x = {}, y = {}, rule = {}\n", x, y, rule));
 
         let mut wrapped = "".to_string();
         let mut index = 0;
         for char in rle.chars() {
             if index <= 70 {
-                wrapped.push_str(&char.to_string());
+                wrapped.push(char);
                 index += 1;
             }
             else {
@@ -188,9 +374,9 @@ impl Life {
         output.join("\n")
     }
 
-    pub fn parse_life106(text: String) -> Vec<i32> {
+    pub fn parse_life106(text: String) -> Vec<i64> {
         let lines = text.split('\n');
-        let mut positions: Vec<i32> = Vec::new();
+        let mut positions: Vec<i64> = Vec::new();
 
         let pattern_106 = r"\s*\-?[0-9]+\s+\-?[0-9]+\s*";
         let re = Regex::new(pattern_106).unwrap();
@@ -204,8 +390,8 @@ impl Life {
             else if re.is_match(line) {
                 let parts: Vec<&str> = line.split("").collect();
                 for i in 0..&parts.len() / 2 {
-                    let x = parts[2 * i].parse::<i32>().unwrap();
-                    let y = parts[2 * i + 1].parse::<i32>().unwrap();
+                    let x = parts[2 * i].parse::<i64>().unwrap();
+                    let y = parts[2 * i + 1].parse::<i64>().unwrap();
                     positions.push(x);
                     positions.push(y);
                 }
@@ -214,4 +400,215 @@ impl Life {
 
         positions
     }
-}
\ No newline at end of file
+
+    /// The cells of an apgcode such as `xs4_33`, `xp2_7` or `xq4_153`.
+    pub fn parse_apgcode(code: String) -> Result<Vec<i64>, String> {
+        let (prefix, wechsler) = code.trim().split_once('_').ok_or_else(|| format!("Invalid apgcode {}", code))?;
+        if !["xs", "xp", "xq"].iter().any(|kind| prefix.starts_with(kind)) {
+            return Err(format!("Unknown apgcode prefix {}", prefix));
+        }
+
+        let pts = from_wechsler(wechsler)?;
+        Ok(pts.into_iter().flat_map(|(x, y)| [x, y]).collect())
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    /// Loads the object of an apgcode, keeping the rule.
+    pub fn load_apgcode(&mut self, code: String) -> Result<(), String> {
+        let positions = Life::parse_apgcode(code)?;
+        let states = vec![1; positions.len() / 2];
+        self.edit(|universe| {
+            universe.replace_pattern(positions, states);
+            Ok(())
+        })
+    }
+
+    /// The apgcode of the pattern, which has to repeat within `max_period`
+    /// generations, see `to_apgcode`.
+    pub fn apgcode(&self, max_period: u64) -> Result<String, String> {
+        self.selection_apgcode(i64::MIN, i64::MAX, i64::MIN, i64::MAX, max_period)
+    }
+
+    /// The apgcode of the cells between the given bounds, ordered like
+    /// `get_bounds`.
+    pub fn selection_apgcode(&self, min_x: i64, max_x: i64, min_y: i64, max_y: i64, max_period: u64) -> Result<String, String> {
+        if self.topology.is_bounded() {
+            return Err("Apgcodes are for patterns on an infinite plane".to_string());
+        }
+        if self.engine().background() {
+            return Err("Apgcodes need the background to be dead".to_string());
+        }
+
+        let cells: Vec<(i64, i64, u8)> = self.engine().cells().into_iter()
+            .filter(|&(x, y, _)| (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y))
+            .collect();
+        to_apgcode(&self.rule, &cells, max_period)
+    }
+
+    /// Loads an RLE pattern, switching to the rule given in its header. When
+    /// that rule can't be run, such as `LifeHistory`, the cells are loaded
+    /// under the current rule anyway and the error says so.
+    pub fn load_rle(&mut self, rle: String) -> Result<(), String> {
+        let (positions, states, rule) = Life::parse_rle_pattern(&rle);
+        let mut rule_error = None;
+        self.edit(|universe| {
+            if let Some(rule) = rule {
+                rule_error = universe.replace_rule(&rule).err();
+            }
+            universe.replace_pattern(positions, states);
+            universe.generation = parse_rle_generation(&rle).unwrap_or(0);
+            Ok(())
+        })?;
+
+        match rule_error {
+            Some(e) => Err(format!("Loaded the pattern under {} instead: {}", self.rule(), e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the pattern as RLE with its rule, and its generation so that
+    /// `load_rle` can pick up where it left off.
+    pub fn save_rle(&self, comment_string: String) -> String {
+        let cells = self.engine().cells();
+        let pts = cells.iter().flat_map(|&(x, y, _)| [x, y]).collect();
+        let states = cells.iter().map(|&(_, _, state)| state).collect();
+        let rle = Life::convert_rle_states(pts, states, comment_string, self.rule());
+        match self.generation() {
+            0 => rle,
+            generation => format!("#CXRLE Gen={}\n{}", generation, rle),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODES: [&str; 4] = ["xs4_33", "xp2_7", "xq4_153", "xq4_6frc"];
+
+    fn cells(code: &str) -> Vec<(i64, i64, u8)> {
+        Life::parse_apgcode(code.to_string()).unwrap().chunks(2).map(|c| (c[0], c[1], 1)).collect()
+    }
+
+    #[test]
+    fn saves_and_loads_the_rule_in_rle() {
+        let mut universe = Universe::new();
+        universe.set_rule("B36/S23").unwrap();
+        universe.construct(vec![0, 0, 1, 0, 2, 0, 2, 3]);
+        let rle = universe.save_rle("a blinker and a cell".to_string());
+        assert!(rle.contains("x = 3, y = 4, rule = B36/S23\n"), "{}", rle);
+
+        let mut loaded = Universe::new();
+        loaded.load_rle(rle).unwrap();
+        assert_eq!(loaded.rule(), "B36/S23");
+        assert_eq!(loaded.expand(), universe.expand());
+    }
+
+    #[test]
+    fn saves_and_loads_decaying_cells_in_rle() {
+        let states = |universe: &Universe| {
+            let mut cells = universe.engine().cells();
+            let min_x = cells.iter().map(|c| c.0).min().unwrap();
+            let min_y = cells.iter().map(|c| c.1).min().unwrap();
+            cells.iter_mut().for_each(|c| *c = (c.0 - min_x, c.1 - min_y, c.2));
+            cells.sort();
+            cells
+        };
+
+        let mut universe = Universe::new();
+        universe.set_rule("B2/S/C3").unwrap();
+        universe.construct_states(vec![0, 0, 1, 0, 2, 0], vec![1, 2, 1]);
+        assert!(universe.save_rle(String::new()).ends_with("\nABA!"));
+
+        // star wars leaves a trail of cells in both decaying states
+        universe.set_rule("B2/S345/C4").unwrap();
+        universe.construct_states(vec![0, 0, 1, 0, 0, 1, 1, 1, 3, 1, 0, 3], vec![1, 1, 1, 2, 3, 1]);
+        universe.advance(12);
+        let rle = universe.save_rle(String::new());
+        let mut loaded = Universe::new();
+        loaded.load_rle(rle.clone()).unwrap();
+        assert!(states(&universe).iter().any(|c| c.2 == 3), "{}", rle);
+        assert_eq!(states(&loaded), states(&universe), "{}", rle);
+
+        // states past 24 take a prefix
+        universe.set_rule("B2/S/C40").unwrap();
+        universe.construct_states(vec![0, 0, 1, 0, 3, 0], vec![25, 39, 1]);
+        assert!(universe.save_rle(String::new()).ends_with("\npApO.A!"));
+    }
+
+    #[test]
+    fn loads_cells_under_the_current_rule_when_the_header_rule_fails() {
+        let mut universe = Universe::new();
+        assert!(universe.load_rle("x = 3, y = 1, rule = LifeHistory\n3o!".to_string()).is_err());
+        assert_eq!((universe.rule().as_str(), universe.population()), ("B3/S23", 3));
+    }
+
+    #[test]
+    fn loads_an_empty_rle_as_an_empty_universe() {
+        let mut universe = Universe::new();
+        universe.construct(vec![0, 0, 1, 0, 2, 0]);
+        universe.load_rle("x = 0, y = 0, rule = B36/S23\n!".to_string()).unwrap();
+        assert_eq!((universe.rule().as_str(), universe.population()), ("B36/S23", 0));
+        assert_eq!(Life::convert_rle(Vec::new(), String::new(), "B3/S23".to_string()), "#C \nx = 0, y = 0, rule = B3/S23\n\n!");
+    }
+
+    #[test]
+    fn decodes_apgcodes() {
+        let glider: Vec<(i64, i64)> = cells("xq4_153").iter().map(|&(x, y, _)| (x, y)).collect();
+        assert_eq!(glider, [(0, 0), (1, 0), (1, 2), (2, 0), (2, 1)]);
+        assert_eq!(cells("xs4_33").len(), 4);
+        assert_eq!(cells("xp2_7").len(), 3);
+        // the phase of the lightweight spaceship with the shortest code
+        assert_eq!(cells("xq4_6frc").len(), 12);
+    }
+
+    #[test]
+    fn encodes_decoded_apgcodes() {
+        for code in CODES {
+            assert_eq!(to_apgcode(&Rule::life(), &cells(code), 16), Ok(code.to_string()));
+        }
+    }
+
+    #[test]
+    fn encodes_every_phase_and_orientation_the_same() {
+        let rule = Rule::life();
+        for code in CODES {
+            for phase in crate::census::evolve(&rule, &cells(code), 3) {
+                for map in crate::census::symmetries(&rule) {
+                    let moved: Vec<(i64, i64, u8)> = phase.iter().map(|&(x, y, state)| {
+                        let (x, y) = map(x, y);
+                        (x + 100, y - 7, state)
+                    }).collect();
+                    assert_eq!(to_apgcode(&rule, &moved, 16), Ok(code.to_string()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_wechsler_gaps() {
+        let pts = vec![(0, 0), (2, 0), (5, 4), (45, 6), (46, 12)];
+        let code = to_wechsler(&pts);
+        let mut decoded = from_wechsler(&code).unwrap();
+        decoded.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(decoded, pts);
+    }
+
+    #[test]
+    fn rejects_invalid_apgcodes() {
+        assert!(Life::parse_apgcode("33".to_string()).is_err());
+        assert!(Life::parse_apgcode("ov_s4".to_string()).is_err());
+        assert!(Life::parse_apgcode("xs4_3!".to_string()).is_err());
+        assert!(to_apgcode(&Rule::life(), &[], 16).is_err());
+    }
+
+    #[test]
+    fn loads_and_saves_apgcodes() {
+        for code in CODES {
+            let mut universe = Universe::new();
+            universe.load_apgcode(code.to_string()).unwrap();
+            assert_eq!(universe.apgcode(16), Ok(code.to_string()));
+        }
+    }
+}
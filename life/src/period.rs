@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::algorithm::Algorithm;
+use crate::hashlife::Hashlife;
+use crate::rule::Rule;
+use crate::topology::Topology;
+use crate::Universe;
+
+/// What a pattern settles into, see `Period`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Periodicity {
+    /// Every cell dies, or turns into the background of a `B0` rule.
+    Empty,
+    StillLife,
+    Oscillator,
+    /// Repeats somewhere else, `dx` and `dy` cells away.
+    Spaceship,
+}
+
+/// How a pattern repeats. It first does so after `start` generations, and
+/// then every `period` generations, moving by `dx` and `dy` each time.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Period {
+    pub kind: Periodicity,
+    pub start: u64,
+    pub period: u64,
+    pub dx: i64,
+    pub dy: i64,
+}
+
+/// Runs the cells for up to `max_generations` and returns how they repeat,
+/// or `None` if they don't within that time. Patterns are compared by the
+/// hash of their quadtree moved to the origin, so a spaceship is found
+/// wherever it goes. Spaceships on a bounded grid come back where they
+/// started, so they show up as oscillators there.
+pub fn find_period(rule: Rule, topology: Topology, cells: &[(i64, i64, u8)], background: bool, max_generations: u64) -> Option<Period> {
+    let mut hashlife = Hashlife::new();
+    hashlife.set_rule(rule, topology).ok()?;
+    hashlife.load(cells, background);
+
+    // the generation each shape was first seen, and where
+    let mut seen: HashMap<(u64, bool), (u64, [i64; 2])> = HashMap::new();
+    for generation in 0..=max_generations {
+        let Some((hash, [x, y])) = hashlife.shape() else {
+            return Some(Period { kind: Periodicity::Empty, start: generation, period: 1, dx: 0, dy: 0 });
+        };
+
+        if let Some((start, [start_x, start_y])) = seen.insert((hash, hashlife.background()), (generation, [x, y])) {
+            let (period, dx, dy) = (generation - start, x - start_x, y - start_y);
+            let kind = if dx != 0 || dy != 0 {
+                Periodicity::Spaceship
+            }
+            else if period == 1 {
+                Periodicity::StillLife
+            }
+            else {
+                Periodicity::Oscillator
+            };
+            return Some(Period { kind, start, period, dx, dy });
+        }
+        hashlife.step(1);
+    }
+    None
+}
+
+#[wasm_bindgen]
+impl Universe {
+    /// Runs a copy of the pattern for up to `max_generations` and tells
+    /// whether it is, or settles into, a still life, an oscillator or a
+    /// spaceship. `None` if it doesn't repeat within that time.
+    pub fn find_period(&self, max_generations: u64) -> Option<Period> {
+        let engine = self.engine();
+        find_period(self.rule, self.topology, &engine.cells(), engine.background(), max_generations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Life;
+
+    fn period(code: &str) -> Option<Period> {
+        let cells: Vec<(i64, i64, u8)> = Life::parse_apgcode(code.to_string()).unwrap().chunks(2).map(|c| (c[0], c[1], 1)).collect();
+        find_period(Rule::life(), Topology::default(), &cells, false, 16)
+    }
+
+    #[test]
+    fn finds_the_period_of_known_objects() {
+        let block = Period { kind: Periodicity::StillLife, start: 0, period: 1, dx: 0, dy: 0 };
+        let blinker = Period { kind: Periodicity::Oscillator, start: 0, period: 2, dx: 0, dy: 0 };
+        assert_eq!(period("xs4_33"), Some(block));
+        assert_eq!(period("xp2_7"), Some(blinker));
+
+        let glider = period("xq4_153").unwrap();
+        assert_eq!((glider.kind, glider.start, glider.period), (Periodicity::Spaceship, 0, 4));
+        assert_eq!((glider.dx.abs(), glider.dy.abs()), (1, 1));
+
+        let lwss = period("xq4_6frc").unwrap();
+        assert_eq!((lwss.kind, lwss.start, lwss.period), (Periodicity::Spaceship, 0, 4));
+        assert_eq!((lwss.dx.abs() + lwss.dy.abs(), lwss.dx * lwss.dy), (2, 0));
+    }
+
+    #[test]
+    fn finds_when_a_pattern_settles() {
+        let mut universe = Universe::new();
+        for (x, y) in [(1, 0), (0, 1), (1, 1), (2, 1)] {
+            universe.set_cell(x, y, true);
+        }
+        // a T-tetromino turns into a traffic light
+        let traffic_light = Period { kind: Periodicity::Oscillator, start: 9, period: 2, dx: 0, dy: 0 };
+        assert_eq!(universe.find_period(16), Some(traffic_light));
+        assert_eq!(universe.find_period(9), None);
+
+        universe.set_cell(1, 0, false);
+        universe.set_cell(1, 1, false);
+        assert_eq!(universe.find_period(16).map(|period| period.kind), Some(Periodicity::Empty));
+        assert_eq!(Universe::new().find_period(0).map(|period| (period.kind, period.start)), Some((Periodicity::Empty, 0)));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::algorithm::Algorithm;
+use crate::leaf;
+use crate::rule::Rule;
+use crate::stats::Stats;
+use crate::topology::Topology;
+
+/// The direct engine, which steps every cell of the pattern a block at a time
+/// like Golly's QuickLife. It remembers nothing from one generation to the
+/// next, so it is slower than hashlife on patterns that repeat but much
+/// faster on chaotic soups that hardly ever do.
+///
+/// Cells are kept in tiles of 8x8, which a round of up to four generations
+/// turns into the middle 8x8 cells of every 2x2 block of tiles, so every
+/// other round the tiles start half a tile past multiples of 8.
+#[derive(Debug, Clone)]
+pub struct QuickLife {
+    rule: Rule,
+    phases: [Rule; 2],
+    background: bool,
+    tiles: HashMap<(i64, i64), Tile>,
+    shifted: bool,
+}
+
+// the cells of a tile are kept like those of a leaf, a bit for every cell that
+// is not dead and the states only when some of them are decaying
+#[derive(Debug, Clone, Default)]
+struct Tile {
+    bits: u64,
+    states: Option<Box<[u8; 64]>>,
+}
+
+impl Tile {
+    fn new(cells: &[u8; 64]) -> Tile {
+        Tile {
+            bits: (0..64).filter(|&i| cells[i] != 0).fold(0, |acc, i| acc | 1 << i),
+            states: cells.iter().any(|&state| state > 1).then(|| Box::new(*cells)),
+        }
+    }
+
+    fn cells(&self) -> [u8; 64] {
+        match &self.states {
+            Some(states) => **states,
+            None => std::array::from_fn(|i| (self.bits >> i & 1) as u8),
+        }
+    }
+}
+
+impl Default for QuickLife {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuickLife {
+    pub fn new() -> QuickLife {
+        QuickLife {
+            rule: Rule::default(),
+            phases: [Rule::default().emulate(false), Rule::default().emulate(true)],
+            background: false,
+            tiles: HashMap::new(),
+            shifted: false,
+        }
+    }
+
+    /// Every tile that has live cells, as the position of its top left cell
+    /// and its cells in reading order.
+    pub fn tiles(&self) -> impl Iterator<Item = ((i64, i64), [u8; 64])> + '_ {
+        self.tiles.iter().map(|(&position, tile)| (self.corner(position), tile.cells()))
+    }
+
+    /// Roughly the memory that the tiles take up.
+    pub fn bytes(&self) -> usize {
+        let decaying = self.tiles.values().filter(|tile| tile.states.is_some()).count();
+        self.tiles.len() * std::mem::size_of::<((i64, i64), Tile)>() + decaying * 64
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        Stats { quicklife: true, tiles: self.tiles.len(), bytes: self.bytes(), ..Stats::default() }
+    }
+
+    fn corner(&self, (x, y): (i64, i64)) -> (i64, i64) {
+        let offset = if self.shifted { 4 } else { 0 };
+        (x * 8 + offset, y * 8 + offset)
+    }
+
+    // the tile holding a cell and the index of the cell in it
+    fn locate(&self, x: i64, y: i64) -> ((i64, i64), usize) {
+        let offset = if self.shifted { 4 } else { 0 };
+        let (x, y) = (x - offset, y - offset);
+        ((x.div_euclid(8), y.div_euclid(8)), (y.rem_euclid(8) * 8 + x.rem_euclid(8)) as usize)
+    }
+
+    // steps one generation of each of `generations`, at most 4, which is as far
+    // as the middle of a block can be stepped without the cells around it
+    fn round(&mut self, generations: usize) {
+        // each generation's rule, see `Rule::background_after`
+        let rules: Vec<Rule> = (0..generations)
+            .map(|g| self.phases[usize::from(self.rule.background_after(self.background, g as u64))])
+            .collect();
+
+        // only blocks with a live tile in them can have live cells in the middle
+        let blocks: HashSet<(i64, i64)> = self.tiles.keys()
+            .flat_map(|&(x, y)| [(x - 1, y - 1), (x, y - 1), (x - 1, y), (x, y)])
+            .collect();
+        let shift = i64::from(self.shifted);
+        let empty = Tile::default();
+
+        self.tiles = blocks.into_iter().filter_map(|(x, y)| {
+            let quadrants = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+                .map(|position| self.tiles.get(&position).unwrap_or(&empty));
+            let tile = if self.rule.states() == 2 && quadrants.iter().all(|tile| tile.states.is_none()) {
+                Tile { bits: leaf::step_bits(&rules, quadrants.map(|tile| tile.bits)), states: None }
+            }
+            else {
+                Tile::new(&leaf::step_cells(&rules, leaf::grid(quadrants.map(Tile::cells))))
+            };
+            (tile.bits != 0).then_some(((x + shift, y + shift), tile))
+        }).collect();
+
+        self.shifted = !self.shifted;
+        self.background = self.rule.background_after(self.background, generations as u64);
+    }
+}
+
+impl Algorithm for QuickLife {
+    fn set_rule(&mut self, rule: Rule, topology: Topology) -> Result<(), String> {
+        if topology.is_bounded() {
+            return Err("QuickLife only runs on an infinite plane".to_string());
+        }
+        if self.background && rule.states() > 2 {
+            return Err("Generations rules need the background to be dead".to_string());
+        }
+        self.rule = rule;
+        self.phases = [rule.emulate(false), rule.emulate(true)];
+        Ok(())
+    }
+
+    fn load(&mut self, cells: &[(i64, i64, u8)], background: bool) {
+        self.tiles = HashMap::new();
+        self.shifted = false;
+        self.background = false;
+        for &(x, y, state) in cells {
+            self.set_cell(x, y, state);
+        }
+        self.background = background;
+    }
+
+    fn cells(&self) -> Vec<(i64, i64, u8)> {
+        let mut positions: Vec<(i64, i64)> = self.tiles.keys().copied().collect();
+        positions.sort_by_key(|&(x, y)| (y, x));
+
+        positions.into_iter().flat_map(|position| {
+            let (left, top) = self.corner(position);
+            let cells = self.tiles[&position].cells();
+            (0..64).filter(move |&i| cells[i] != 0).map(move |i| (left + i as i64 % 8, top + i as i64 / 8, cells[i]))
+        }).collect()
+    }
+
+    fn background(&self) -> bool { self.background }
+
+    fn step(&mut self, mut n: u64) {
+        while n > 0 {
+            let generations = n.min(4);
+            self.round(generations as usize);
+            n -= generations;
+        }
+    }
+
+    fn get_cell(&self, x: i64, y: i64) -> u8 {
+        let (position, i) = self.locate(x, y);
+        let state = self.tiles.get(&position).map_or(0, |tile| tile.cells()[i]);
+        state ^ u8::from(self.background)
+    }
+
+    fn set_cell(&mut self, x: i64, y: i64, state: u8) {
+        let (position, i) = self.locate(x, y);
+        let mut cells = self.tiles.get(&position).map_or([0; 64], Tile::cells);
+        cells[i] = state ^ u8::from(self.background);
+
+        let tile = Tile::new(&cells);
+        if tile.bits == 0 {
+            self.tiles.remove(&position);
+        }
+        else {
+            self.tiles.insert(position, tile);
+        }
+    }
+
+    fn bounds(&self) -> Option<[i64; 4]> {
+        self.tiles.iter().map(|(&position, tile)| {
+            let (left, top) = self.corner(position);
+            let columns = (0..8).fold(0, |acc, y| acc | (tile.bits >> (8 * y)) as u8);
+            [
+                left + i64::from(columns.trailing_zeros()),
+                left + 7 - i64::from(columns.leading_zeros()),
+                top + i64::from(tile.bits.trailing_zeros() / 8),
+                top + 7 - i64::from(tile.bits.leading_zeros() / 8),
+            ]
+        }).reduce(|a, b| [a[0].min(b[0]), a[1].max(b[1]), a[2].min(b[2]), a[3].max(b[3])])
+    }
+
+    fn population(&self) -> u64 {
+        if self.background {
+            return u64::MAX;
+        }
+        self.tiles.values().map(|tile| u64::from(tile.bits.count_ones())).sum()
+    }
+}
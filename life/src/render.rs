@@ -1,7 +1,7 @@
-use once_cell::sync::Lazy;
 use wasm_bindgen::prelude::*;
-use crate::{Node, OptionExt, NODE};
-use std::sync::{Arc, Mutex};
+use crate::leaf::LEAF_LEVEL;
+use crate::arena::{Arena, NodeId};
+use crate::Universe;
 
 // #[wasm_bindgen]
 // extern "C" {
@@ -34,192 +34,286 @@ pub struct Renderer {
     image_data_pixels: Vec<u32>,
     image_data_bytes: Vec<u8>,
     added_cells: Vec<(i32, i32, f32)>,
+    state_colors: Vec<u32>,
+    inverted: bool,
+    hex: bool,
 }
 
-pub static RENDERER: Lazy<Mutex<Renderer>> = Lazy::new(|| { Mutex::new(Renderer {
-    background_color: 0x000000FF,
-    cell_color: 0xFFFFFFFF,
-    added_cell_color: 0xFFFFFFFF,
-
-    canvas_width: 0,
-    canvas_height: 0,
-    canvas_offset_x: 0,
-    canvas_offset_y: 0,
-
-    border_width: 0.1,
-    border_pixels: 0,
-    cell_width: 32.0,
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    image_data_pixels: Vec::new(),
-    image_data_bytes: Vec::new(),
-    added_cells: Vec::new(),
-}) });
+// mixes two RGBA colours, t = 0 gives `from` and t = 1 gives `to`
+fn blend(from: u32, to: u32, t: f32) -> u32 {
+    let channel = |shift: u32| {
+        let a = ((from >> shift) & 0xFF) as f32;
+        let b = ((to >> shift) & 0xFF) as f32;
+        ((a + (b - a) * t).round() as u32) << shift
+    };
+    channel(24) | channel(16) | channel(8) | channel(0)
+}
 
 pub fn pixel_to_cell(renderer: &Renderer, x: i32, y: i32) -> (i32, i32) {
+    let y = ((y as f32 - renderer.canvas_offset_y as f32 + renderer.border_width / 2.0) / renderer.cell_width).round() as i32;
     (
-        ((x as f32 - renderer.canvas_offset_x as f32 + renderer.border_width / 2.0) / renderer.cell_width).round() as i32,
-        ((y as f32 - renderer.canvas_offset_y as f32 + renderer.border_width / 2.0) / renderer.cell_width).round() as i32,
+        ((x as f32 - renderer.canvas_offset_x as f32 + renderer.border_width / 2.0) / renderer.cell_width + renderer.shear(y as f32)).round() as i32,
+        y,
     )
 }
 pub fn cell_to_pixel(renderer: &Renderer, x: i32, y: i32) -> (i32, i32) {
     (
-        (x as f32 * renderer.cell_width + renderer.canvas_offset_x as f32 - renderer.border_width / 2.0).round() as i32,
+        ((x as f32 - renderer.shear(y as f32)) * renderer.cell_width + renderer.canvas_offset_x as f32 - renderer.border_width / 2.0).round() as i32,
         (y as f32 * renderer.cell_width + renderer.canvas_offset_y as f32 - renderer.border_width / 2.0).round() as i32,
     )
 }
 
+impl Renderer {
+    // how far a row is shifted left, in the units of `y`
+    fn shear(&self, y: f32) -> f32 {
+        if self.hex { y / 2.0 } else { 0.0 }
+    }
+}
+
 #[wasm_bindgen]
 impl Renderer {
-    pub fn set_background_color(color: u32) {
-        RENDERER.lock().unwrap().background_color = color;
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Renderer {
+        Renderer {
+            background_color: 0x000000FF,
+            cell_color: 0xFFFFFFFF,
+            added_cell_color: 0xFFFFFFFF,
+
+            canvas_width: 0,
+            canvas_height: 0,
+            canvas_offset_x: 0,
+            canvas_offset_y: 0,
+
+            border_width: 0.1,
+            border_pixels: 0,
+            cell_width: 32.0,
+
+            image_data_pixels: Vec::new(),
+            image_data_bytes: Vec::new(),
+            added_cells: Vec::new(),
+            state_colors: Vec::new(),
+            inverted: false,
+            hex: false,
+        }
+    }
+
+    pub fn set_background_color(&mut self, color: u32) {
+        self.background_color = color;
+    }
+    pub fn set_cell_color(&mut self, color: u32) {
+        self.cell_color = color;
     }
-    pub fn set_cell_color(color: u32) {
-        RENDERER.lock().unwrap().cell_color = color;
+
+    /// Shears each row half a cell to the left of the one above it, so that
+    /// patterns of hexagonal rules look hexagonal.
+    pub fn set_hex(&mut self, hex: bool) {
+        self.hex = hex;
     }
 
-    fn zoom(renderer: &mut Renderer, out: bool, center_x: i32, center_y: i32) {
+    fn zoom(&mut self, out: bool, center_x: i32, center_y: i32) {
         if out {
-            renderer.canvas_offset_x -= (renderer.canvas_offset_x - center_x) / 2;
-            renderer.canvas_offset_y -= (renderer.canvas_offset_y - center_y) / 2;
+            self.canvas_offset_x -= (self.canvas_offset_x - center_x) / 2;
+            self.canvas_offset_y -= (self.canvas_offset_y - center_y) / 2;
 
-            renderer.cell_width /= 2.0;
+            self.cell_width /= 2.0;
         }
         else {
-            renderer.canvas_offset_x += renderer.canvas_offset_x - center_x;
-            renderer.canvas_offset_y += renderer.canvas_offset_y - center_y;
+            self.canvas_offset_x += self.canvas_offset_x - center_x;
+            self.canvas_offset_y += self.canvas_offset_y - center_y;
 
-            renderer.cell_width *= 2.0;
+            self.cell_width *= 2.0;
         }
     }
 
-    pub fn zoom_at(out: bool, center_x: i32, center_y: i32) {
-        let mut renderer = RENDERER.lock().unwrap();
-        Self::zoom(&mut renderer, out, center_x, center_y);
+    pub fn zoom_at(&mut self, out: bool, center_x: i32, center_y: i32) {
+        self.zoom(out, center_x, center_y);
     }
-    pub fn zoom_centered(out: bool) {
-        let mut renderer = RENDERER.lock().unwrap();
-        let (width, height) = (renderer.canvas_width, renderer.canvas_height);
-        Self::zoom(&mut renderer, out, width >> 1, height >> 1);
+    pub fn zoom_centered(&mut self, out: bool) {
+        let (width, height) = (self.canvas_width, self.canvas_height);
+        self.zoom(out, width >> 1, height >> 1);
     }
-    pub fn zoom_to(level: f32) {
-        RENDERER.lock().unwrap().cell_width = level;
+    pub fn zoom_to(&mut self, level: f32) {
+        self.cell_width = level;
     }
 
-    pub fn move_offset(x: i32, y: i32) {
-        let mut renderer = RENDERER.lock().unwrap();
-        renderer.canvas_offset_x += x;
-        renderer.canvas_offset_y += y;
+    pub fn move_offset(&mut self, x: i32, y: i32) {
+        self.canvas_offset_x += x;
+        self.canvas_offset_y += y;
     }
 
-    pub fn set_size(width: i32, height: i32) {
-        let mut renderer = RENDERER.lock().unwrap();
-        renderer.canvas_width = width;
-        renderer.canvas_height = height;
+    pub fn set_size(&mut self, width: i32, height: i32) {
+        self.canvas_width = width;
+        self.canvas_height = height;
     }
 
-    pub fn center_view(offset_x: i32, offset_y: i32) {
-        let mut renderer = RENDERER.lock().unwrap();
-        renderer.canvas_offset_x = renderer.canvas_width >> 1;
-        renderer.canvas_offset_y = renderer.canvas_height >> 1;
+    pub fn center_view(&mut self, offset_x: i32, offset_y: i32) {
+        self.canvas_offset_x = self.canvas_width >> 1;
+        self.canvas_offset_y = self.canvas_height >> 1;
+
+        self.canvas_offset_x += -offset_x;
+        self.canvas_offset_y += -offset_y;
+    }
 
-        renderer.canvas_offset_x += -offset_x;
-        renderer.canvas_offset_y += -offset_y;
+    pub fn get_cell_width(&self) -> f32 {
+        self.cell_width
     }
 
-    pub fn get_cell_width() -> f32 {
-        RENDERER.lock().unwrap().cell_width
+    /// Returns the `[x, y]` cell underneath a canvas pixel.
+    pub fn cell_at(&self, x: i32, y: i32) -> Vec<i32> {
+        let cell = pixel_to_cell(self, x, y);
+        vec![cell.0, cell.1]
     }
 
-    pub fn draw_cell(x: i32, y: i32) {
-        let mut renderer = RENDERER.lock().unwrap();
-        let width = renderer.cell_width;
-        let cells = pixel_to_cell(&renderer, x, y);
-        let pixels = cell_to_pixel(&renderer, cells.0, cells.1);
+    pub fn draw_cell(&mut self, x: i32, y: i32) {
+        let width = self.cell_width;
+        let cells = pixel_to_cell(self, x, y);
+        let pixels = cell_to_pixel(self, cells.0, cells.1);
 
-        renderer.added_cells.push((pixels.0, pixels.1, width));
+        self.added_cells.push((pixels.0, pixels.1, width));
     }
 
-    fn draw_square(renderer: &mut Renderer, mut x: i32, mut y: i32, size: f32, color: u32) {
-        let mut width = size.round() as i32 - renderer.border_pixels;
-        let mut height = width;
+    fn draw_square(&mut self, x: i32, y: i32, size: f32, color: u32) {
+        let width = size.round() as i32 - self.border_pixels;
+        self.draw_rect(x, y, width, width, color);
+    }
 
+    fn draw_rect(&mut self, mut x: i32, mut y: i32, mut width: i32, mut height: i32, color: u32) {
         if x < 0 {
             width += x;
             x = 0;
         }
-        if x + width > renderer.canvas_width {
-            width = renderer.canvas_width - x;
+        if x + width > self.canvas_width {
+            width = self.canvas_width - x;
         }
 
         if y < 0 {
             height += y;
             y = 0;
         }
-        if y + height > renderer.canvas_height {
-            height = renderer.canvas_height - y;
+        if y + height > self.canvas_height {
+            height = self.canvas_height - y;
         }
-    
+
         if width <= 0 || height <= 0 {
             return;
         }
 
-        let mut pointer = x + y * renderer.canvas_width;
-        let row_width = renderer.canvas_width - width;
+        let mut pointer = x + y * self.canvas_width;
+        let row_width = self.canvas_width - width;
 
         for _ in 0..height {
             for _ in 0..width {
-                renderer.image_data_pixels[pointer as usize] = color;
+                self.image_data_pixels[pointer as usize] = color;
                 pointer += 1;
             }
             pointer += row_width;
         }
     }
 
-    fn draw_node(renderer: &mut Renderer, node: Option<Arc<Node>>, mut size: f32, left: f32, top: f32) {
-        if node.population() == 0 { return };
+    fn is_offscreen(&self, size: f32, left: f32, top: f32) -> bool {
+        left + size - self.shear(top) + (self.canvas_offset_x as f32) < 0.0 ||
+            top + size + (self.canvas_offset_y as f32) < 0.0 ||
+            left - self.shear(top + size) + (self.canvas_offset_x as f32) >= self.canvas_width as f32 ||
+            top + (self.canvas_offset_y as f32) >= self.canvas_height as f32
+    }
 
-        if left + size + (renderer.canvas_offset_x as f32) < 0.0 ||
-            top + size + (renderer.canvas_offset_y as f32) < 0.0 ||
-            left + (renderer.canvas_offset_x as f32) >= renderer.canvas_width as f32 ||
-            top + (renderer.canvas_offset_y as f32) >= renderer.canvas_height as f32 { return };
+    // draws the 8x8 cells of a leaf or a QuickLife tile
+    fn draw_leaf(&mut self, cells: &[u8; 64], mut size: f32, left: f32, top: f32) {
+        if self.is_offscreen(size, left, top) { return };
 
-        if size <= 1.0 {
-            if node.population() > 0 {
-                Self::draw_square(renderer, left.round() as i32 + renderer.canvas_offset_x, top.round() as i32 + renderer.canvas_offset_y, 1.0, renderer.cell_color);
+        size /= 8.0;
+        for (i, &state) in cells.iter().enumerate().filter(|(_, &state)| state != 0) {
+            let (left, top) = (left + (i % 8) as f32 * size, top + (i / 8) as f32 * size);
+            let x = (left - self.shear(top)).round() as i32 + self.canvas_offset_x;
+            let (size, color) = if size <= 1.0 {
+                (1.0, if self.inverted { self.background_color } else { self.cell_color })
             }
+            else {
+                (self.cell_width, self.state_colors.get(state as usize).copied().unwrap_or(self.cell_color))
+            };
+            self.draw_square(x, top.round() as i32 + self.canvas_offset_y, size, color);
         }
-        else if node.level() == 0 {
-            if node.population() > 0 {
-                Self::draw_square(renderer, left.round() as i32 + renderer.canvas_offset_x, top.round() as i32 + renderer.canvas_offset_y, renderer.cell_width, renderer.cell_color);
+    }
+
+    fn draw_node(&mut self, nodes: &Arena, id: NodeId, mut size: f32, left: f32, top: f32) {
+        let node = &nodes[id];
+        if node.population == 0 { return };
+
+        if self.is_offscreen(size, left, top) { return };
+
+        let x = (left - self.shear(top)).round() as i32 + self.canvas_offset_x;
+        if size <= 1.0 {
+            if node.population > 0 {
+                let color = if self.inverted { self.background_color } else { self.cell_color };
+                self.draw_square(x, top.round() as i32 + self.canvas_offset_y, 1.0, color);
             }
         }
+        else if node.level == LEAF_LEVEL {
+            self.draw_leaf(&nodes.cells(id), size, left, top);
+        }
         else {
             size /= 2.0;
 
-            Self::draw_node(renderer, node.a(), size, left, top);
-            Self::draw_node(renderer, node.b(), size, left + size, top);
-            Self::draw_node(renderer, node.c(), size, left, top + size);
-            Self::draw_node(renderer, node.d(), size, left + size, top + size);
+            self.draw_node(nodes, node.a, size, left, top);
+            self.draw_node(nodes, node.b, size, left + size, top);
+            self.draw_node(nodes, node.c, size, left, top + size);
+            self.draw_node(nodes, node.d, size, left + size, top + size);
         }
     }
 
-    pub fn get_image_data() -> *const u8 {
-        let mut renderer = RENDERER.lock().unwrap();
-        let node = NODE.lock().unwrap();
+    pub fn get_image_data(&mut self, universe: &Universe) -> *const u8 {
+        self.image_data_pixels = vec![self.background_color; (self.canvas_width * self.canvas_height) as usize];
+        self.border_pixels = (self.border_width * self.cell_width).floor() as i32;
+
+        // decaying states fade from the cell colour towards the background
+        let states = universe.states();
+        self.state_colors = (0..states).map(|state| match state {
+            0 => self.background_color,
+            _ => blend(self.cell_color, self.background_color, (state - 1) as f32 / (states - 1) as f32),
+        }).collect();
+
+        // while a B0 rule has the background on, stored cells are the dead ones
+        self.inverted = universe.is_background_alive();
+        if self.inverted {
+            self.state_colors.swap(0, 1);
+            let cell_color = self.cell_color;
+            match universe.topology_bounds() {
+                Some((left, top, width, height)) => {
+                    let (x, y) = cell_to_pixel(self, left, top);
+                    let size = (width as f32 * self.cell_width, height as f32 * self.cell_width);
+                    self.draw_rect(x, y, size.0.round() as i32, size.1.round() as i32, cell_color);
+                }
+                None => self.image_data_pixels.fill(cell_color),
+            }
+        }
+
+        match universe.quicklife() {
+            Some(quicklife) => {
+                for ((x, y), cells) in quicklife.tiles() {
+                    let (left, top) = (x as f32 * self.cell_width, y as f32 * self.cell_width);
+                    self.draw_leaf(&cells, 8.0 * self.cell_width, left, top);
+                }
+            }
+            None => {
+                let (nodes, node) = (universe.hashlife().nodes(), universe.hashlife().root());
+                let size = 2.0_f32.powf(nodes[node].level as f32 - 1.0) * self.cell_width;
+                self.draw_node(nodes, node, size * 2.0, -size, -size);
+            }
+        }
 
-        renderer.image_data_pixels = vec![renderer.background_color; (renderer.canvas_width * renderer.canvas_height) as usize];
-        renderer.border_pixels = (renderer.border_width * renderer.cell_width).floor() as i32;
-        
-        let size = 2.0_f32.powf(node.level() as f32 - 1.0) * renderer.cell_width;
-        Self::draw_node(&mut renderer, node.clone(), size * 2.0, -size, -size);
-        
-        for (x, y, width) in renderer.added_cells.drain(..).collect::<Vec<_>>() {
-            let new_cell_color = renderer.added_cell_color;
-            Self::draw_square(&mut renderer, x, y, width, new_cell_color);
+        for (x, y, width) in self.added_cells.drain(..).collect::<Vec<_>>() {
+            let new_cell_color = self.added_cell_color;
+            self.draw_square(x, y, width, new_cell_color);
         }
-        
-        renderer.image_data_bytes = renderer.image_data_pixels.iter().flat_map(|val| val.to_be_bytes()).collect();
 
-        renderer.image_data_bytes.as_ptr()
+        self.image_data_bytes = self.image_data_pixels.iter().flat_map(|val| val.to_be_bytes()).collect();
+
+        self.image_data_bytes.as_ptr()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,483 @@
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::str::FromStr;
+
+/// A rule on the Moore neighborhood, written in B/S notation. Counts may be
+/// followed by Hensel letters (`B2n3/S23-q`) to make the rule isotropic
+/// non-totalistic.
+///
+/// Neighborhoods are 9-bit masks over the 3x3 block in reading order, so the
+/// center cell is bit 4:
+///
+/// ```text
+/// 0 1 2
+/// 3 4 5
+/// 6 7 8
+/// ```
+///
+/// Bit `n` of `table` is set when a cell whose neighborhood of live cells is
+/// `n` is alive in the next generation.
+///
+/// Generations rules have more than two `states`: a live cell that does not
+/// survive steps through states 2, 3, .. before it turns off, and only cells in
+/// state 1 count as neighbors.
+///
+/// Rules with `B0` turn the empty background on, which the universe emulates by
+/// storing cells relative to the background, see `Rule::emulate`.
+///
+/// An `H` or `V` suffix switches to the hexagonal or von Neumann neighborhood,
+/// in which case the cells outside the neighborhood are ignored by `table`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rule {
+    table: [u64; 8],
+    states: u8,
+    neighborhood: Neighborhood,
+    // the birth and survival counts when `table` only depends on how many
+    // neighbors are alive, worked out once from the table
+    counts: Option<(u16, u16)>,
+}
+
+/// The cells around a cell that count as its neighbors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Neighborhood {
+    Moore,
+    /// Hexagonal cells on the square grid, ignoring the NE and SW corners. Row
+    /// `y` has to be drawn shifted left by `y / 2` cells to look hexagonal.
+    Hexagonal,
+    VonNeumann,
+}
+
+const CENTER: usize = 1 << 4;
+
+impl Neighborhood {
+    // the bits of the 3x3 block that are neighbors
+    fn mask(self) -> usize {
+        match self {
+            Neighborhood::Moore => 0x1FF & !CENTER,
+            Neighborhood::Hexagonal => 0x1FF & !CENTER & !(1 << 2) & !(1 << 6),
+            Neighborhood::VonNeumann => 1 << 1 | 1 << 3 | 1 << 5 | 1 << 7,
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Neighborhood::Moore => "",
+            Neighborhood::Hexagonal => "H",
+            Neighborhood::VonNeumann => "V",
+        }
+    }
+}
+
+// one neighborhood for each Hensel letter with 1 to 4 live neighbors, the
+// letters for 5 to 7 neighbors are those of the complementary neighborhood
+static HENSEL: [&[(char, usize)]; 5] = [
+    &[],
+    &[('c', 1), ('e', 2)],
+    &[('c', 5), ('e', 10), ('a', 3), ('i', 40), ('k', 33), ('n', 68)],
+    &[('c', 69), ('e', 42), ('a', 11), ('i', 7), ('k', 98), ('n', 13), ('j', 14), ('q', 70), ('r', 41), ('y', 97)],
+    &[('c', 325), ('e', 170), ('a', 15), ('i', 45), ('k', 99), ('n', 71), ('j', 106), ('q', 102), ('r', 43),
+      ('y', 101), ('t', 105), ('w', 78), ('z', 108)],
+];
+
+// the Hensel letter of every neighborhood without the center cell
+static LETTERS: Lazy<[Option<char>; 512]> = Lazy::new(|| {
+    let mut letters = [None; 512];
+    for (count, classes) in HENSEL.iter().enumerate() {
+        for &(letter, neighborhood) in classes.iter() {
+            for n in symmetries(neighborhood) {
+                letters[n] = Some(letter);
+                if count < 4 {
+                    letters[!n & 0x1FF & !CENTER] = Some(letter);
+                }
+            }
+        }
+    }
+    letters
+});
+
+// names are matched after lowercasing and dropping spaces, dashes, apostrophes and '&'
+static ALIASES: [(&str, &str); 18] = [
+    ("life", "B3/S23"),
+    ("conway", "B3/S23"),
+    ("highlife", "B36/S23"),
+    ("daynight", "B3678/S34678"),
+    ("seeds", "B2/S"),
+    ("lifewithoutdeath", "B3/S012345678"),
+    ("2x2", "B36/S125"),
+    ("34life", "B34/S34"),
+    ("maze", "B3/S12345"),
+    ("replicator", "B1357/S1357"),
+    ("diamoeba", "B35678/S5678"),
+    ("morley", "B368/S245"),
+    ("anneal", "B4678/S35678"),
+    ("briansbrain", "B2/S/C3"),
+    ("starwars", "B2/S345/C4"),
+    ("frogs", "B34/S12/C3"),
+    ("tlife", "B3/S2-i34q"),
+    ("justfriends", "B2-a/S12"),
+];
+
+// the neighborhood under each of the 8 rotations and reflections of the square
+fn symmetries(neighborhood: usize) -> impl Iterator<Item = usize> {
+    (0..8).map(move |symmetry| {
+        (0..9).filter(|i| neighborhood & (1 << i) != 0).fold(0, |acc, i| {
+            let (mut row, mut col) = (i / 3, i % 3);
+            if symmetry & 4 != 0 {
+                col = 2 - col;
+            }
+            for _ in 0..(symmetry & 3) {
+                (row, col) = (col, 2 - row);
+            }
+            acc | 1 << (row * 3 + col)
+        })
+    })
+}
+
+// parses counts like "2n3" or "23-q" into the neighborhoods (without the
+// center cell) they cover
+fn parse_neighborhoods(spec: &str, neighborhood: Neighborhood) -> Result<Vec<usize>, String> {
+    let mask = neighborhood.mask();
+    let mut neighborhoods = Vec::new();
+    let mut chars = spec.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        let count = match char.to_digit(10) {
+            Some(n) if n <= mask.count_ones() => n,
+            _ => return Err(format!("Invalid neighbor count '{}'", char)),
+        };
+        let negate = chars.next_if_eq(&'-').is_some();
+        let mut letters = String::new();
+        while let Some(letter) = chars.next_if(|c| c.is_ascii_lowercase()) {
+            letters.push(letter);
+        }
+
+        if neighborhood != Neighborhood::Moore && !letters.is_empty() {
+            return Err("Hensel letters need the Moore neighborhood".to_string());
+        }
+        let classes = HENSEL[count.min(8 - count) as usize];
+        if let Some(letter) = letters.chars().find(|l| !classes.iter().any(|(c, _)| c == l)) {
+            return Err(format!("Invalid letter '{}' for {} neighbors", letter, count));
+        }
+        if negate && letters.is_empty() {
+            return Err(format!("Missing letters after '{}-'", count));
+        }
+
+        for n in (0..512).filter(|n| n & CENTER == 0 && (n & mask).count_ones() == count) {
+            let listed = LETTERS[n].is_some_and(|l| letters.contains(l));
+            if letters.is_empty() || listed != negate {
+                neighborhoods.push(n);
+            }
+        }
+    }
+    Ok(neighborhoods)
+}
+
+// writes the counts, and the letters where only some neighborhoods of a count
+// are included, for the neighborhoods with the given center
+fn format_neighborhoods(rule: &Rule, center: usize) -> String {
+    let mask = rule.neighborhood.mask();
+    let mut output = String::new();
+    for count in 0..=mask.count_ones() {
+        let neighborhoods: Vec<usize> = (0..512)
+            .filter(|n| n & CENTER == 0 && (n & mask).count_ones() == count && rule.get(n | center))
+            .collect();
+        if neighborhoods.is_empty() {
+            continue;
+        }
+        if rule.neighborhood != Neighborhood::Moore {
+            output.push_str(&count.to_string());
+            continue;
+        }
+
+        let classes: Vec<char> = HENSEL[count.min(8 - count) as usize].iter().map(|&(l, _)| l).collect();
+        let (included, excluded): (Vec<char>, Vec<char>) = classes.iter()
+            .partition(|&&l| neighborhoods.iter().any(|&n| LETTERS[n] == Some(l)));
+
+        output.push_str(&count.to_string());
+        if !excluded.is_empty() {
+            if included.len() <= excluded.len() {
+                output.extend(included);
+            }
+            else {
+                output.push('-');
+                output.extend(excluded);
+            }
+        }
+    }
+    output
+}
+
+fn parse_states(digits: &str) -> Result<u8, String> {
+    match digits.parse::<u8>() {
+        Ok(states) if states >= 2 => Ok(states),
+        _ => Err(format!("Invalid number of states '{}'", digits)),
+    }
+}
+
+impl Rule {
+    pub fn life() -> Rule {
+        "B3/S23".parse().unwrap()
+    }
+
+    fn from_neighborhoods(birth: &[usize], survival: &[usize], states: u8, neighborhood: Neighborhood) -> Rule {
+        let mut rule = Rule { table: [0; 8], states, neighborhood, counts: None };
+        for &n in birth {
+            rule.table[n >> 6] |= 1 << (n & 63);
+        }
+        for &n in survival {
+            rule.table[(n | CENTER) >> 6] |= 1 << ((n | CENTER) & 63);
+        }
+        rule.with_counts()
+    }
+
+    fn with_counts(mut self) -> Rule {
+        let mut counts = [None; 18];
+        for n in 0..512 {
+            let count = (n & self.neighborhood.mask()).count_ones() as usize + if n & CENTER != 0 { 9 } else { 0 };
+            if *counts[count].get_or_insert(self.get(n)) != self.get(n) {
+                self.counts = None;
+                return self;
+            }
+        }
+        let mask = |counts: &[Option<bool>]| (0..9).filter(|&i| counts[i] == Some(true)).fold(0, |acc, i| acc | 1 << i);
+        self.counts = Some((mask(&counts[..9]), mask(&counts[9..])));
+        self
+    }
+
+    fn get(&self, neighborhood: usize) -> bool {
+        self.table[neighborhood >> 6] & (1 << (neighborhood & 63)) != 0
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The bits of the 3x3 block that are neighbors, see `Neighborhood`.
+    pub fn neighbors(&self) -> usize {
+        self.neighborhood.mask()
+    }
+
+    /// Bit `n` of the birth and survival masks is set when `n` live neighbors
+    /// turn a cell on or keep it on, for rules that only count neighbors.
+    pub fn counts(&self) -> Option<(u16, u16)> {
+        self.counts
+    }
+
+    /// Whether the background is alive after a generation, given whether it is
+    /// alive now.
+    pub fn next_background(&self, background: bool) -> bool {
+        self.get(if background { 0x1FF } else { 0 })
+    }
+
+    /// Whether the background is alive `generations` from now. Under B0 rules
+    /// it can flip every generation, and with it the rule that steps the
+    /// stored cells, so engines look up each generation's rule from this.
+    pub fn background_after(&self, mut background: bool, generations: u64) -> bool {
+        // a map on two values repeats itself after at most two steps
+        let generations = if generations < 3 { generations } else { 2 - generations % 2 };
+        for _ in 0..generations {
+            background = self.next_background(background);
+        }
+        background
+    }
+
+    /// The rule for cells stored relative to the background, so that a stored
+    /// cell is alive where it differs from `background`. It never turns on an
+    /// empty neighborhood, even for `B0` rules, and is the rule itself while
+    /// the background is dead and the rule has no `B0`.
+    pub fn emulate(&self, background: bool) -> Rule {
+        let flip = if background { 0x1FF } else { 0 };
+        let next = self.next_background(background);
+
+        let mut rule = Rule { table: [0; 8], ..*self };
+        for n in (0..512).filter(|n| self.get(n ^ flip) != next) {
+            rule.table[n >> 6] |= 1 << (n & 63);
+        }
+        rule.with_counts()
+    }
+
+    /// `neighborhood` has a bit set for each cell of the 3x3 block that is in
+    /// state 1.
+    pub fn next_state(&self, state: u8, neighborhood: usize) -> u8 {
+        match state {
+            0 | 1 if self.get(neighborhood) => 1,
+            0 => 0,
+            _ if state + 1 < self.states => state + 1,
+            _ => 0,
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::life()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name: String = s.to_lowercase().chars().filter(|c| !" -_&'".contains(*c)).collect();
+        if let Some((_, rule)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+            return rule.parse();
+        }
+
+        let s = s.trim();
+        let (s, neighborhood) = match s.strip_suffix(['H', 'h']) {
+            Some(s) => (s, Neighborhood::Hexagonal),
+            None => match s.strip_suffix(['V', 'v']) {
+                Some(s) => (s, Neighborhood::VonNeumann),
+                None => (s, Neighborhood::Moore),
+            },
+        };
+        let (birth, survival, states) = if s.starts_with(['B', 'b', 'S', 's']) {
+            // B36/S23, S23/B36, b3s23, B2n3/S23-q, B2/S/C3 or B2/S34H
+            let mut birth = None;
+            let mut survival = None;
+            let mut states = None;
+            let mut rest = s;
+            while !rest.is_empty() {
+                let prefix = rest.chars().next().unwrap().to_ascii_uppercase();
+                let end = rest[1..]
+                    .find(|c: char| c == '/' || c.is_ascii_uppercase() || "bsg".contains(c))
+                    .map_or(rest.len(), |i| i + 1);
+                let spec = &rest[1..end];
+                match prefix {
+                    'B' if birth.is_none() => birth = Some(parse_neighborhoods(spec, neighborhood)?),
+                    'S' if survival.is_none() => survival = Some(parse_neighborhoods(spec, neighborhood)?),
+                    'C' | 'G' if states.is_none() => states = Some(parse_states(spec)?),
+                    _ => return Err(format!("Invalid rule '{}'", s)),
+                }
+                rest = rest[end..].trim_start_matches('/');
+            }
+            (birth.unwrap_or_default(), survival.unwrap_or_default(), states.unwrap_or(2))
+        }
+        else {
+            // 23/3 or 345/2/4
+            let parse = |spec| parse_neighborhoods(spec, neighborhood);
+            match s.split('/').collect::<Vec<_>>()[..] {
+                [survival, birth] => (parse(birth)?, parse(survival)?, 2),
+                [survival, birth, states] => (parse(birth)?, parse(survival)?, parse_states(states)?),
+                _ => return Err(format!("Invalid rule '{}'", s)),
+            }
+        };
+
+        if birth.contains(&0) && states > 2 {
+            return Err("B0 is not supported in Generations rules".to_string());
+        }
+        Ok(Rule::from_neighborhoods(&birth, &survival, states, neighborhood))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "B{}/S{}", format_neighborhoods(self, 0), format_neighborhoods(self, CENTER))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        write!(f, "{}", self.neighborhood.suffix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_rules_in_canonical_form() {
+        for (rule, formatted) in [
+            ("B3/S23", "B3/S23"),
+            ("b3s23", "B3/S23"),
+            ("S23/B3", "B3/S23"),
+            ("23/3", "B3/S23"),
+            ("345/2/4", "B2/S345/C4"),
+            ("B2/S/C3", "B2/S/C3"),
+            ("Brian's Brain", "B2/S/C3"),
+            ("B2/S34H", "B2/S34H"),
+            ("B13/S012V", "B13/S012V"),
+            ("B2n3/S23-q", "B2n3/S23-q"),
+            ("tlife", "B3/S2-i34q"),
+            ("B3-cnqy/S2-a3", "B3-cnqy/S2-a3"),
+            ("B4ceijkqrtwyz/S", "B4-an/S"),
+            ("B3aceijknqry/S23", "B3/S23"),
+        ] {
+            let parsed: Rule = rule.parse().unwrap();
+            assert_eq!(parsed.to_string(), formatted, "{}", rule);
+            assert_eq!(formatted.parse::<Rule>(), Ok(parsed), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn counts_only_the_cells_of_the_neighborhood() {
+        // with B1 a single neighbor gives a birth exactly when it counts
+        for (rule, neighbors) in [("B1/S", 0x1EF), ("B1/SH", 0x1AB), ("B1/SV", 0xAA)] {
+            let rule: Rule = rule.parse().unwrap();
+            assert_eq!(rule.neighbors(), neighbors, "{}", rule);
+            for i in (0..9).filter(|&i| i != 4) {
+                assert_eq!(rule.next_state(0, 1 << i) == 1, neighbors >> i & 1 == 1, "{} {}", rule, i);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_every_hensel_letter() {
+        let mut classes = 0;
+        for count in 0..=8 {
+            let letters: Vec<char> = HENSEL[count.min(8 - count)].iter().map(|&(letter, _)| letter).collect();
+            classes += letters.len().max(1);
+            if letters.is_empty() {
+                continue;
+            }
+
+            for birth in [true, false] {
+                let spec = |counts: String| if birth { format!("B{}/S", counts) } else { format!("B/S{}", counts) };
+                let all: Rule = spec(count.to_string()).parse().unwrap();
+                let mut union = Rule { table: [0; 8], ..all };
+                for &letter in &letters {
+                    let spec = |negate: &str| spec(format!("{}{}{}", count, negate, letter));
+                    let only: Rule = spec("").parse().unwrap();
+                    let except: Rule = spec("-").parse().unwrap();
+                    assert_eq!(only.to_string().parse::<Rule>(), Ok(only), "{}", spec(""));
+                    assert_eq!(except.to_string().parse::<Rule>(), Ok(except), "{}", spec("-"));
+                    assert_eq!(only.counts(), None, "{}", spec(""));
+
+                    // a letter and the rest of its count cover the count between them
+                    for i in 0..8 {
+                        assert_eq!(only.table[i] & except.table[i], 0, "{}", spec(""));
+                        assert_eq!(only.table[i] | except.table[i], all.table[i], "{}", spec(""));
+                        union.table[i] |= only.table[i];
+                    }
+                }
+                assert_eq!(union.with_counts(), all, "{}", spec(count.to_string()));
+            }
+        }
+        assert_eq!(classes, 51);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in ["B9/S23", "B2x/S23", "B2-/S23", "B2n/S3H", "B3/S23V/C1", "B0/S/C3", "B3/S23/B6", "3/2/1/0", "B5V"] {
+            assert!(rule.parse::<Rule>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn emulates_b0_rules_on_a_dead_background() {
+        for rule in ["B013/S3", "B0123478/S34678", "B02ck/S2-c", "B03/S012345678"] {
+            let rule: Rule = rule.parse().unwrap();
+            for background in [false, true] {
+                let emulated = rule.emulate(background);
+                let flip = if background { 0x1FF } else { 0 };
+                assert!(!emulated.next_background(false), "{}", rule);
+                for n in 0..512 {
+                    let alive = emulated.next_state(1 & (n >> 4) as u8, n) == 1;
+                    assert_eq!(alive ^ rule.next_background(background), rule.get(n ^ flip), "{} {}", rule, n);
+                }
+            }
+        }
+
+        let life = Rule::life();
+        assert_eq!(life.emulate(false), life);
+    }
+}
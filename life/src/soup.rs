@@ -0,0 +1,19 @@
+// random patterns for the tests, the same on every run
+
+/// An endless xorshift stream starting from `seed`.
+pub(crate) fn random(mut seed: u64) -> impl Iterator<Item = u64> {
+    std::iter::repeat_with(move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    })
+}
+
+/// The live cells of a `size` by `size` square with its corner at
+/// (`margin`, `margin`), about a quarter of them alive.
+pub(crate) fn soup(size: i64, margin: i64) -> Vec<(i64, i64)> {
+    random(0x9E3779B97F4A7C15).zip(0..size * size)
+        .filter_map(|(seed, i)| (seed >> 62 == 0).then_some((margin + i % size, margin + i / size)))
+        .collect()
+}
@@ -0,0 +1,80 @@
+use wasm_bindgen::prelude::*;
+use crate::Universe;
+
+/// A snapshot of the memory use and cache behaviour of the engine running
+/// one universe.
+#[derive(Debug, Clone, Copy, Default)]
+#[wasm_bindgen]
+pub struct Stats {
+    /// Whether the figures are QuickLife's. It has no nodes or caches, so
+    /// only `tiles` and `bytes` apply and everything else is 0.
+    pub quicklife: bool,
+    /// QuickLife tiles with live cells, 0 under hashlife.
+    pub tiles: usize,
+
+    /// Nodes in the universe's arena, leaves included.
+    pub live_nodes: usize,
+    pub successor_cache: usize,
+    pub zero_cache: usize,
+    /// Nodes above the leaves, each an entry of the join table.
+    pub join_cache: usize,
+
+    pub successor_hits: u64,
+    pub successor_misses: u64,
+    pub join_hits: u64,
+    pub join_misses: u64,
+
+    /// Calls to `successor`, recursive ones included, during the last advance.
+    pub successor_calls: usize,
+    pub bytes: usize,
+}
+
+#[wasm_bindgen]
+impl Universe {
+    pub fn stats(&self) -> Stats {
+        match self.quicklife() {
+            Some(quicklife) => quicklife.stats(),
+            None => self.hashlife().stats(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Universe;
+
+    #[test]
+    fn counts_nodes_and_cache_use() {
+        let mut universe = Universe::new();
+        universe.construct(vec![0, 0, 1, 0, 2, 0]);
+        universe.advance(64);
+        let stats = universe.stats();
+        assert!(stats.successor_misses > 0 && stats.successor_calls > 0);
+        assert!(stats.live_nodes > stats.join_cache && stats.join_misses == stats.live_nodes as u64);
+        assert!(stats.successor_cache > 0 && stats.bytes > 0);
+
+        // a blinker is back where it started after an even number of
+        // generations, so the same jump is all in the cache
+        universe.advance(64);
+        let again = universe.stats();
+        assert_eq!(again.successor_misses, stats.successor_misses);
+        assert_eq!(again.live_nodes, stats.live_nodes);
+        assert!(again.successor_hits > stats.successor_hits);
+        assert!(again.join_hits > stats.join_hits);
+    }
+
+    #[test]
+    fn reports_quicklife_tiles_instead_of_nodes() {
+        let mut universe = Universe::new();
+        universe.construct(vec![0, 0, 1, 0, 2, 0]);
+        universe.advance(64);
+        assert!(!universe.stats().quicklife && universe.stats().tiles == 0);
+
+        universe.set_algorithm("QuickLife").unwrap();
+        universe.advance(64);
+        let stats = universe.stats();
+        assert!(stats.quicklife && stats.tiles > 0 && stats.bytes > 0);
+        assert_eq!((stats.live_nodes, stats.successor_cache, stats.successor_calls), (0, 0, 0));
+        assert_eq!((stats.successor_hits, stats.join_misses), (0, 0));
+    }
+}
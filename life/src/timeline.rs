@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use crate::history::Snapshot;
+
+/// Snapshots taken while the pattern runs, at least `interval` generations
+/// apart, so that any generation since the last edit can be brought back by
+/// stepping forward from the keyframe before it. They are taken between
+/// steps rather than in the middle of one, so a big step stays one jump.
+///
+/// When the keyframes go over their budget, the interval doubles and the
+/// ones closer together than that are dropped, so they keep covering the
+/// whole run evenly. The first keyframe, where the run started, is never
+/// dropped.
+#[derive(Debug)]
+pub struct Timeline {
+    keyframes: BTreeMap<u64, Snapshot>,
+    // the interval set by hand, `interval` grows from it as keyframes thin out
+    base_interval: u64,
+    interval: u64,
+    budget: usize,
+}
+
+impl Timeline {
+    pub fn new(interval: u64, budget: usize) -> Timeline {
+        Timeline { keyframes: BTreeMap::new(), base_interval: interval, interval, budget }
+    }
+
+    /// Generations between keyframes, 0 when there are none.
+    pub fn interval(&self) -> u64 { self.interval }
+
+    pub fn budget(&self) -> usize { self.budget }
+
+    pub fn set_budget(&mut self, bytes: usize) {
+        self.budget = bytes;
+    }
+
+    pub fn len(&self) -> usize { self.keyframes.len() }
+
+    pub fn generations(&self) -> impl Iterator<Item = u64> + '_ {
+        self.keyframes.keys().copied()
+    }
+
+    pub fn snapshots(&self) -> impl Iterator<Item = &Snapshot> {
+        self.keyframes.values()
+    }
+
+    /// Whether a keyframe belongs at `generation`, an interval or more after
+    /// the latest one.
+    pub fn is_due(&self, generation: u64) -> bool {
+        match self.keyframes.keys().next_back() {
+            Some(&latest) => self.interval != 0 && generation >= latest.saturating_add(self.interval),
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, snapshot: Snapshot) {
+        self.keyframes.insert(snapshot.generation, snapshot);
+    }
+
+    /// The latest keyframe at or before `generation`, taken out so that it
+    /// can be restored. Put it back with `insert`.
+    pub fn take_before(&mut self, generation: u64) -> Option<Snapshot> {
+        let (&start, _) = self.keyframes.range(..=generation).next_back()?;
+        self.keyframes.remove(&start)
+    }
+
+    /// Doubles the interval and drops the keyframes that come sooner than
+    /// that after the one kept before them.
+    pub fn thin(&mut self) -> Vec<Snapshot> {
+        self.interval = self.interval.saturating_mul(2);
+        let mut kept: Option<u64> = None;
+        let mut dropped = Vec::new();
+        for &generation in self.keyframes.keys() {
+            match kept {
+                Some(kept) if generation < kept.saturating_add(self.interval) => dropped.push(generation),
+                _ => kept = Some(generation),
+            }
+        }
+        dropped.into_iter().filter_map(|generation| self.keyframes.remove(&generation)).collect()
+    }
+
+    /// Forgets every keyframe and goes back to the interval set by hand, or
+    /// to a new one.
+    pub fn clear(&mut self, interval: Option<u64>) -> Vec<Snapshot> {
+        self.base_interval = interval.unwrap_or(self.base_interval);
+        self.interval = self.base_interval;
+        std::mem::take(&mut self.keyframes).into_values().collect()
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The shape of the universe, written as a Golly style suffix after the rule
+/// (`B3/S23:T30,20`).
+///
+/// Bounded grids are `width` by `height` cells, each at most `i32::MAX`, with
+/// their top left cell at `(-width / 2, -height / 2)`:
+///
+/// - `:P30,20` is a plane whose edges are dead
+/// - `:T30,20` is a torus, opposite edges are joined
+/// - `:K30*,20` is a Klein bottle, like the torus but the edges marked with
+///   `*` are joined with a twist, here the top and bottom edges
+/// - `:C30,20` is a cross-surface, both pairs of edges are twisted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    #[default]
+    Infinite,
+    Plane(i32, i32),
+    Torus(i32, i32),
+    Klein(i32, i32, Twist),
+    CrossSurface(i32, i32),
+}
+
+/// Which edges of a Klein bottle are joined with a twist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Twist {
+    TopBottom,
+    LeftRight,
+}
+
+impl Topology {
+    /// The left, top, width and height of a bounded grid.
+    pub fn bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        match *self {
+            Topology::Infinite => None,
+            Topology::Plane(width, height) |
+            Topology::Torus(width, height) |
+            Topology::Klein(width, height, _) |
+            Topology::CrossSurface(width, height) => Some((-(width / 2), -(height / 2), width, height)),
+        }
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        *self != Topology::Infinite
+    }
+
+    // whether the left and right edges (and the top and bottom edges) are
+    // joined, and whether they are twisted
+    fn edges(&self) -> ((bool, bool), (bool, bool)) {
+        match *self {
+            Topology::Infinite | Topology::Plane(..) => ((false, false), (false, false)),
+            Topology::Torus(..) => ((true, false), (true, false)),
+            Topology::Klein(.., Twist::TopBottom) => ((true, false), (true, true)),
+            Topology::Klein(.., Twist::LeftRight) => ((true, true), (true, false)),
+            Topology::CrossSurface(..) => ((true, true), (true, true)),
+        }
+    }
+
+    /// Maps a cell just outside the grid, relative to its top left cell, to the
+    /// cell inside it that lies there once the edges are joined. Cells beyond
+    /// dead edges map to `None`.
+    pub fn wrap(&self, mut x: i32, mut y: i32) -> Option<(i32, i32)> {
+        let (_, _, width, height) = self.bounds()?;
+        let ((left_right, twist_left_right), (top_bottom, twist_top_bottom)) = self.edges();
+
+        if x < 0 || x >= width {
+            if !left_right {
+                return None;
+            }
+            if twist_left_right {
+                y = height - 1 - y;
+            }
+            x = x.rem_euclid(width);
+        }
+        if y < 0 || y >= height {
+            if !top_bottom {
+                return None;
+            }
+            if twist_top_bottom {
+                x = width - 1 - x;
+            }
+            y = y.rem_euclid(height);
+        }
+        Some((x, y))
+    }
+}
+
+fn parse_size(digits: &str) -> Result<i32, String> {
+    match digits.parse::<i32>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!("Invalid grid size '{}'", digits)),
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    /// Parses the part after the colon, `T30,20` or `T30` for a square grid. An
+    /// empty string is the infinite plane.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        let kind = match chars.next() {
+            None => return Ok(Topology::Infinite),
+            Some(kind) => kind.to_ascii_uppercase(),
+        };
+
+        let size = chars.as_str();
+        // a square grid like `K30*` has its one size, and its twist, on the width
+        let (width, height, square) = match size.split_once(',') {
+            Some((width, height)) => (width, height, false),
+            None => (size, size, true),
+        };
+        let twist = match (width.ends_with('*'), height.ends_with('*') && !square) {
+            (true, false) => Some(Twist::TopBottom),
+            (false, true) => Some(Twist::LeftRight),
+            (false, false) => None,
+            (true, true) => return Err(format!("Invalid grid '{}'", s)),
+        };
+        let (width, height) = (parse_size(width.trim_end_matches('*'))?, parse_size(height.trim_end_matches('*'))?);
+
+        match (kind, twist) {
+            ('P', None) => Ok(Topology::Plane(width, height)),
+            ('T', None) => Ok(Topology::Torus(width, height)),
+            ('K', twist) => Ok(Topology::Klein(width, height, twist.unwrap_or(Twist::TopBottom))),
+            ('C', None) => Ok(Topology::CrossSurface(width, height)),
+            _ => Err(format!("Invalid grid '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Topology::Infinite => Ok(()),
+            Topology::Plane(width, height) => write!(f, ":P{},{}", width, height),
+            Topology::Torus(width, height) => write!(f, ":T{},{}", width, height),
+            Topology::Klein(width, height, Twist::TopBottom) => write!(f, ":K{}*,{}", width, height),
+            Topology::Klein(width, height, Twist::LeftRight) => write!(f, ":K{},{}*", width, height),
+            Topology::CrossSurface(width, height) => write!(f, ":C{},{}", width, height),
+        }
+    }
+}
@@ -1,13 +1,15 @@
 import { useState, useEffect } from 'react'
 import './App.css'
 
-import init, { Life, Renderer, init_panic_hook } from 'life';
+import init, { Life, Universe as LifeUniverse, Renderer, init_panic_hook } from 'life';
 
 import Universe from './Universe';
 
 function App() {
   const [wasmInitialized, setWasmInitialized] = useState(false);
   const [memory, setMemory] = useState(null);
+  const [universe, setUniverse] = useState(null);
+  const [renderer, setRenderer] = useState(null);
 
   useEffect(() => {
     init().then((InitOutput) => {
@@ -15,6 +17,8 @@ function App() {
       console.log('WASM loaded');
       setWasmInitialized(true);
       setMemory(InitOutput.memory);
+      setUniverse(new LifeUniverse());
+      setRenderer(new Renderer());
     });
   }, []);
 
@@ -22,7 +26,7 @@ function App() {
     <div className="App">
       {wasmInitialized ? 
       <div>
-        <Universe Life={Life} Renderer={Renderer} memory={memory}/> 
+        <Universe Life={Life} universe={universe} renderer={renderer} memory={memory}/> 
       </div>
       : 
       <div>Loading...</div>}
@@ -1,6 +1,6 @@
 import { useState, useEffect } from 'react'
 
-function Controls({ offset, zoomOut, centerView, step, play, playing }) {
+function Controls({ offset, zoomOut, centerView, step, play, playing, rule, setRule, stepExponent, changeStep, hyperspeed, setHyperspeed, algorithm, setAlgorithm, undo }) {
   return (
     <div className="Controls">
       <nav>
@@ -34,6 +34,40 @@ function Controls({ offset, zoomOut, centerView, step, play, playing }) {
               playing ? <button onClick={() => play(false)}>pause</button> : <button onClick={() => play(true)}>play</button>
             }
           </li>
+          <li>
+            <button onClick={() => undo(false)}>undo</button>
+          </li>
+          <li>
+            <button onClick={() => undo(true)}>redo</button>
+          </li>
+          <li>
+            <button onClick={() => changeStep(false)}>slower</button>
+          </li>
+          <li>
+            <button onClick={() => changeStep(true)}>faster</button>
+          </li>
+          <li>
+            <span>step 2^{stepExponent}</span>
+          </li>
+          <li>
+            <label>
+              <input type="checkbox" checked={hyperspeed} onChange={(e) => setHyperspeed(e.target.checked)} />
+              hyper
+            </label>
+          </li>
+          <li>
+            <select value={algorithm} onChange={(e) => setAlgorithm(e.target.value)}>
+              <option value="HashLife">HashLife</option>
+              <option value="QuickLife">QuickLife</option>
+            </select>
+          </li>
+          <li>
+            <input
+              key={rule}
+              defaultValue={rule}
+              onKeyDown={(e) => { if (e.key === 'Enter') setRule(e.target.value) }}
+            />
+          </li>
         </ul>
       </nav>
     </div>
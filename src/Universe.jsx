@@ -1,7 +1,7 @@
 import { useState, useEffect, useRef } from 'react'
 import Controls from './Controls';
 
-function Universe({ Life, Renderer, memory }) {
+function Universe({ Life, universe, renderer, memory }) {
   const canvasRef = useRef(null);
   const isStepping = useRef(false);
   const animationFrame = useRef(null);
@@ -17,53 +17,109 @@ function Universe({ Life, Renderer, memory }) {
   }
 
   const [fpsInfo, setFpsInfo] = useState('');
+  const [rule, setRuleText] = useState(universe.rule());
+  const [stepExponent, setStepExponent] = useState(universe.step_exponent());
+  const [hyperspeed, _setHyperspeed] = useState(universe.hyperspeed());
+  const [algorithm, _setAlgorithm] = useState(universe.algorithm());
 
   function loadRle(pattern) {
-    const items = Life.parse_rle(pattern);
-    Life.construct(items);
+    try {
+      universe.load_rle(pattern);
+    }
+    catch (e) {
+      console.error(e);
+    }
+    setRuleText(universe.rule());
+  }
+
+  function setRule(rule) {
+    try {
+      universe.set_rule(rule);
+    }
+    catch (e) {
+      console.error(e);
+    }
+    setRuleText(universe.rule());
   }
 
+  useEffect(() => {
+    // hexagonal rules end in H, before the grid if there is one
+    renderer.set_hex(rule.split(':')[0].endsWith('H'));
+  }, [rule]);
+
   function resizeCanvas() {
     const canvas = canvasRef.current;
     const width = document.documentElement.clientWidth;
     const height = document.documentElement.clientHeight;
     canvas.width = width;
     canvas.height = height;
-    Renderer.set_size(canvas.width, canvas.height, 1);
-    // Renderer.set_size(canvas.width, canvas.height, window.devicePixelRatio);
+    renderer.set_size(canvas.width, canvas.height, 1);
+    // renderer.set_size(canvas.width, canvas.height, window.devicePixelRatio);
     if (!playing) draw();
   }
 
   function offset(direction) {
     switch (direction) {
       case 'up':
-        Renderer.move_offset(0, 100);
+        renderer.move_offset(0, 100);
         break;
       case 'down':
-        Renderer.move_offset(0, -100);
+        renderer.move_offset(0, -100);
         break;
       case 'left':
-        Renderer.move_offset(100, 0);
+        renderer.move_offset(100, 0);
         break;
       case 'right':
-        Renderer.move_offset(-100, 0);
+        renderer.move_offset(-100, 0);
         break;
     }
     if (!playing) draw();
   }
 
   function zoomOut(out) {
-    Renderer.zoom_centered(out);
+    renderer.zoom_centered(out);
     if (!playing) draw();
   }
 
   function step() {
-    Life.advance(1);
+    universe.tick();
+    setStepExponent(universe.step_exponent());
     if (!playing) draw();
   }
 
+  // undo and redo can bring back another rule or engine too
+  function undo(redo) {
+    if (redo) universe.redo();
+    else universe.undo();
+    setRuleText(universe.rule());
+    _setAlgorithm(universe.algorithm());
+    if (!playing) draw();
+  }
+
+  function changeStep(faster) {
+    const exponent = Math.max(universe.step_exponent() + (faster ? 1 : -1), 0);
+    universe.set_step(universe.step_base(), exponent);
+    setStepExponent(universe.step_exponent());
+  }
+
+  function setHyperspeed(hyperspeed) {
+    universe.set_hyperspeed(hyperspeed);
+    _setHyperspeed(hyperspeed);
+  }
+
+  function setAlgorithm(algorithm) {
+    try {
+      universe.set_algorithm(algorithm);
+    }
+    catch (e) {
+      console.error(e);
+    }
+    _setAlgorithm(universe.algorithm());
+  }
+
   function centerView() {
-    const bounds = Life.get_bounds();
+    // coordinates come back as BigInts, which are plenty precise as numbers on screen
+    const bounds = Array.from(universe.get_bounds(), Number);
     const width = Math.ceil((bounds[0] - bounds[1]) * 1.1);
     const height = Math.ceil((bounds[2] - bounds[3]) * 1.1);
 
@@ -96,8 +152,8 @@ function Universe({ Life, Renderer, memory }) {
       center_y = Math.round((center_y * new_cell_width * 1.1));
     }
 
-    Renderer.zoom_to(new_cell_width);
-    Renderer.center_view(center_x, center_y);
+    renderer.zoom_to(new_cell_width);
+    renderer.center_view(center_x, center_y);
     if (!playing) draw();
   }
 
@@ -113,7 +169,7 @@ function Universe({ Life, Renderer, memory }) {
     if (lastMousePos !== null) {
       let dx = Math.round(e.clientX - lastMousePos[0]);
       let dy = Math.round(e.clientY - lastMousePos[1]);
-      Renderer.move_offset(dx, dy);
+      renderer.move_offset(dx, dy);
       lastMousePos[0] += dx;
       lastMousePos[1] += dy;
     }
@@ -153,12 +209,13 @@ function Universe({ Life, Renderer, memory }) {
 
   function draw() {
     if (queueDrawCell.current) {
-      Renderer.draw_cell(queueDrawCell.current[0], queueDrawCell.current[1])
+      renderer.draw_cell(queueDrawCell.current[0], queueDrawCell.current[1])
     }
 
     if (queueSetCells.current.length > 0) {
       for (let x = 0; x < queueSetCells.current.length; x++) {
-        Life.set_cell(queueSetCells.current[x][0], queueSetCells.current[x][1], queueSetCells.current[x][2]);
+        const cell = renderer.cell_at(queueSetCells.current[x][0], queueSetCells.current[x][1]);
+        universe.set_cell(BigInt(cell[0]), BigInt(cell[1]), queueSetCells.current[x][2]);
       }
       queueSetCells.current = [];
     }
@@ -168,7 +225,7 @@ function Universe({ Life, Renderer, memory }) {
 
     const width = canvas.width;
     const height = canvas.height;
-    const imagePtr = Renderer.get_image_data();
+    const imagePtr = renderer.get_image_data(universe);
     const imageDataArray = new Uint8ClampedArray(memory.buffer, imagePtr, width * height * 4);
     const imageData = new ImageData(imageDataArray, width, height);
     context.putImageData(imageData, 0, 0);
@@ -197,10 +254,11 @@ function Universe({ Life, Renderer, memory }) {
       items.push(pulsar[x] - 20, pulsar[x + 1]);
     }
 
-    Life.construct(items);
-    console.log(Life.level());
-    console.log(Life.expand());
-    console.log(Life.convert_rle(Life.expand(), 'gosper gun'));
+    // coordinates are i64 on the rust side
+    universe.construct(BigInt64Array.from(items, BigInt));
+    console.log(universe.level());
+    console.log(universe.expand());
+    console.log(Life.convert_rle(universe.expand(), 'gosper gun', universe.rule()));
 
     resizeCanvas();
     centerView();
@@ -214,7 +272,7 @@ function Universe({ Life, Renderer, memory }) {
       if (e.which === 3 || e.which === 2) {
         canvas.addEventListener('contextmenu', (e) => e.preventDefault());
 
-        if (Renderer.get_cell_width() >= 1) {
+        if (renderer.get_cell_width() >= 1) {
           drawCells(e);
           window.addEventListener('mousemove', drawCells, true);
         }
@@ -249,7 +307,7 @@ function Universe({ Life, Renderer, memory }) {
     canvas.onmousewheel = (e) => {
       e.preventDefault();
       const mouse_pos = getMousePos(canvas, e);
-      Renderer.zoom_at((e.wheelDelta || -e.detail) < 0, mouse_pos.x, mouse_pos.y);
+      renderer.zoom_at((e.wheelDelta || -e.detail) < 0, mouse_pos.x, mouse_pos.y);
       draw();
       return false;
     };
@@ -274,6 +332,15 @@ function Universe({ Life, Renderer, memory }) {
         step={step}
         play={playWithStep}
         playing={playing}
+        rule={rule}
+        setRule={setRule}
+        stepExponent={stepExponent}
+        changeStep={changeStep}
+        hyperspeed={hyperspeed}
+        setHyperspeed={setHyperspeed}
+        algorithm={algorithm}
+        setAlgorithm={setAlgorithm}
+        undo={undo}
       />
       <canvas className='Canvas' ref={canvasRef} />
       {/* <p>fpsInfo</p> */}